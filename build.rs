@@ -0,0 +1,150 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+//! Creates startup snapshots of the built-in postscripts and extensions at compile time.
+//!
+//! The snapshots are keyed the same way `Runtime` keys them, so a runtime picks them up whenever its scripts match.
+
+use deno_core::{anyhow::Error, Extension, JsRuntime, RuntimeOptions};
+use std::{
+    env, fs, iter,
+    path::{Path, PathBuf},
+};
+
+#[path = "lib/runtime/snapshot_key.rs"]
+mod snapshot_key;
+
+#[path = "lib/runtime/builtins.rs"]
+mod builtins;
+
+use builtins::{BuiltinExtension, WITH_EVENTS_EXTENSIONS, WITH_PERMISSIONS_EXTENSIONS};
+use snapshot_key::SnapshotKey;
+
+// Loaded by the permissions extension, which `RuntimeBuilder` adds to every runtime ahead of the built-in extensions.
+const PERMISSIONS_SCRIPT_PATH: &str = "lib/extensions/permissions/01_permissions.js";
+
+// The built-in extension selections of `Runtime::builder()`, `Runtime::with_permissions` and `Runtime::with_events`.
+const EXTENSION_SETS: &[&[BuiltinExtension]] =
+    &[&[], WITH_PERMISSIONS_EXTENSIONS, WITH_EVENTS_EXTENSIONS];
+
+fn main() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let snapshots_dir = PathBuf::from(env::var("OUT_DIR").unwrap()).join("snapshots");
+
+    println!("cargo:rerun-if-changed=lib/postscripts");
+    println!("cargo:rerun-if-changed=lib/extensions");
+    println!("cargo:rerun-if-changed=lib/runtime/snapshot_key.rs");
+    println!("cargo:rerun-if-changed=lib/runtime/builtins.rs");
+
+    fs::create_dir_all(&snapshots_dir).unwrap();
+
+    let postscripts = read_postscripts(&manifest_dir);
+
+    let mut entries = String::new();
+    for extension_set in EXTENSION_SETS {
+        let extension_scripts = read_extension_scripts(&manifest_dir, extension_set);
+
+        // Snapshot is keyed by every script it contains.
+        let snapshot_key = SnapshotKey::new(
+            extension_scripts
+                .iter()
+                .map(|(name, content)| (*name, content.as_str()))
+                .chain(
+                    postscripts
+                        .iter()
+                        .map(|(name, content)| (name.as_str(), content.as_str())),
+                ),
+        );
+
+        let snapshot_path = snapshots_dir.join(format!("{}.snap", snapshot_key.as_str()));
+        fs::write(
+            &snapshot_path,
+            create_snapshot(extension_scripts, &postscripts),
+        )
+        .unwrap();
+
+        entries.push_str(&format!(
+            "    ({:?}, include_bytes!({:?})),\n",
+            snapshot_key.as_str(),
+            snapshot_path
+        ));
+    }
+
+    fs::write(
+        snapshots_dir.with_file_name("snapshots.rs"),
+        format!(
+            "static EMBEDDED_SNAPSHOTS: &[(&str, &[u8])] = &[\n{}];\n",
+            entries
+        ),
+    )
+    .unwrap();
+}
+
+/// Reads the scripts of the selected extensions in the order `RuntimeBuilder` loads them.
+fn read_extension_scripts(
+    manifest_dir: &Path,
+    extension_set: &[BuiltinExtension],
+) -> Vec<(&'static str, String)> {
+    let builtin_paths = BuiltinExtension::all()
+        .iter()
+        .filter(|builtin| extension_set.contains(builtin))
+        .map(|builtin| builtin.script_path());
+
+    iter::once(PERMISSIONS_SCRIPT_PATH)
+        .chain(builtin_paths)
+        .map(|path| read_extension_script(manifest_dir, path))
+        .collect()
+}
+
+/// Reads an extension script and names it the same way `include_js_files!` does.
+fn read_extension_script(manifest_dir: &Path, path: &str) -> (&'static str, String) {
+    let name = format!("(tera:extensions) /{}", path);
+    let content = fs::read_to_string(manifest_dir.join(path)).unwrap();
+
+    // Script names are static in deno, and the build script is short-lived.
+    (Box::leak(name.into_boxed_str()), content)
+}
+
+fn read_postscripts(manifest_dir: &Path) -> Vec<(String, String)> {
+    let postscripts_dir = manifest_dir.join("lib/postscripts");
+
+    let mut postscripts = fs::read_dir(&postscripts_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+
+    // Sort postscripts by file name.
+    postscripts.sort();
+
+    postscripts
+        .iter()
+        .map(|path| {
+            let content = fs::read_to_string(path).unwrap();
+            let file_name = path.file_name().unwrap().to_str().unwrap();
+            (
+                format!("(tera:postscripts) /lib/postscripts/{}", file_name),
+                content,
+            )
+        })
+        .collect()
+}
+
+fn create_snapshot(
+    extension_scripts: Vec<(&'static str, String)>,
+    postscripts: &[(String, String)],
+) -> Vec<u8> {
+    let mut js_files: Vec<(&'static str, Box<dyn Fn() -> Result<String, Error>>)> = vec![];
+    for (filepath, content) in extension_scripts {
+        js_files.push((filepath, Box::new(move || Ok(content.clone()))));
+    }
+
+    let mut runtime = JsRuntime::new(RuntimeOptions {
+        extensions: vec![Extension::builder().js(js_files).build()],
+        will_snapshot: true,
+        ..Default::default()
+    });
+
+    for (name, content) in postscripts.iter() {
+        runtime.execute_script(name, content).unwrap();
+    }
+
+    runtime.snapshot().to_vec()
+}
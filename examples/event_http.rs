@@ -53,8 +53,15 @@ async fn main() -> Result<()> {
     let events = create_http_events(Rc::new(response_tx))?;
 
     // Create a new runtime.
-    let mut runtime =
-        Runtime::with_events(permissions, events, false, vec![], Default::default()).await?;
+    let mut runtime = Runtime::with_events(
+        permissions,
+        events,
+        false,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+    .await?;
 
     // Read main module code.
     let code = fs::read_to_string("examples/js/event_http.js")?;
@@ -70,7 +77,5 @@ fn create_http_events(response_tx: Rc<Sender<Response<Body>>>) -> Result<Rc<RefC
     let responder = Rc::new(HttpResponder::new(response_tx));
     let http_event = HttpEvent::new(request, responder);
 
-    Ok(Rc::new(RefCell::new(Events {
-        http: Some(http_event),
-    })))
+    Ok(Rc::new(RefCell::new(Events::with_http(http_event))))
 }
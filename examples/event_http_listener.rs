@@ -0,0 +1,76 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+extern crate tera;
+
+use std::{cell::RefCell, fs, rc::Rc};
+
+use tera::{
+    events::{Events, HttpEvent, HttpResponder},
+    permissions::{
+        events::event_http::{self},
+        Permissions,
+    },
+    Runtime,
+};
+use tokio::sync::mpsc;
+use utilities::{
+    hyper::{body, Body, Request, Response},
+    result::Result,
+};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Create permissions
+    let permissions = Permissions::builder()
+        .add_permissions(&[
+            event_http::HttpEvent::RequestRead,
+            event_http::HttpEvent::ResponseWrite,
+            event_http::HttpEvent::ResponseSend,
+        ])?
+        .build();
+
+    // Create channels.
+    let (response_tx, mut response_rx) = mpsc::channel::<Response<Body>>(8);
+    let response_tx = Rc::new(response_tx);
+
+    // The runtime is not created with an event. Events are dispatched to it instead.
+    let events = Rc::new(RefCell::new(Events::default()));
+
+    // Create a new runtime.
+    let mut runtime = Runtime::with_events(
+        permissions,
+        Rc::clone(&events),
+        false,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+    .await?;
+
+    // Read main module code.
+    let code = fs::read_to_string("examples/js/event_http_listener.js")?;
+
+    // Execute main module. It registers a listener.
+    runtime
+        .execute_module("/examples/js/event_http_listener.js", code)
+        .await?;
+
+    // Dispatch several events to the same runtime.
+    for name in ["Alice", "Bob", "Eve"] {
+        let request = Request::builder().body(Body::from(name))?;
+        let responder = Rc::new(HttpResponder::new(Rc::clone(&response_tx)));
+        runtime.dispatch_http_event(HttpEvent::new(request, responder))?;
+    }
+
+    // Handle the events concurrently.
+    runtime.run_event_loop().await?;
+
+    // Print responses.
+    drop(response_tx);
+    while let Some(response) = response_rx.recv().await {
+        let bytes = body::to_bytes(response.into_body()).await?;
+        println!("Response = {:?}", bytes);
+    }
+
+    Ok(())
+}
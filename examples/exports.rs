@@ -0,0 +1,74 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+extern crate tera;
+
+use std::convert::TryFrom;
+
+use serde::{Deserialize, Serialize};
+use tera::{
+    permissions::{
+        fs::{Fs, FsPath, FsRoot},
+        Permissions,
+    },
+    Runtime,
+};
+use tokio::fs;
+use utilities::result::Result;
+
+#[derive(Serialize)]
+struct Input {
+    width: u32,
+    height: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct Output {
+    area: u32,
+    perimeter: u32,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Create permitted resources
+    let allow_list = [FsPath::from("/examples/js/**")];
+
+    // Create permissions
+    let permissions = Permissions::builder()
+        .add_state(FsRoot::try_from(env!("CARGO_MANIFEST_DIR"))?)
+        .add_permissions_with_allow_lists(&[(Fs::Execute, &allow_list)])?
+        .build();
+
+    // Create a new runtime.
+    let mut runtime = Runtime::with_permissions(
+        permissions,
+        false,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+    .await?;
+
+    // Get main module code.
+    let main_module_code = fs::read_to_string("examples/js/exports.js").await?;
+
+    // Load main module.
+    let module_id = runtime
+        .load_module("/examples/js/exports.js", main_module_code)
+        .await?;
+
+    // Call the default export.
+    let output: Output = runtime
+        .call_export(
+            module_id,
+            "default",
+            (Input {
+                width: 5,
+                height: 40,
+            },),
+        )
+        .await?;
+
+    println!("{:?}", output);
+
+    Ok(())
+}
@@ -25,12 +25,19 @@ async fn main() -> Result<()> {
             (Fs::Open, &allow_list),
             (Fs::Read, &allow_list),
             (Fs::Write, &allow_list),
+            (Fs::Info, &allow_list),
         ])?
         .build();
 
     // Create a new runtime.
-    let mut runtime =
-        Runtime::with_permissions(permissions, false, vec![], Default::default()).await?;
+    let mut runtime = Runtime::with_permissions(
+        permissions,
+        false,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+    .await?;
 
     // Get the code.
     let code = fs::read_to_string("examples/js/files.js")?;
@@ -0,0 +1,15 @@
+const {
+  log,
+  decode,
+  events: { http },
+  Response,
+} = Tera;
+
+// Handle each request dispatched to this runtime.
+http.listen(async ({ request, respondWith }) => {
+  const name = decode(await request.body.readAll());
+
+  log.info("handling request for", name);
+
+  await respondWith(new Response(`Hello ${name}!`));
+});
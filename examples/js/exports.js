@@ -0,0 +1,9 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+import { Rectangle } from "./shapes.js";
+
+export default async function handle({ width, height }) {
+  const rect = new Rectangle(width, height);
+
+  return { area: rect.area(), perimeter: rect.perimeter() };
+}
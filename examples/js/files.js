@@ -1,6 +1,6 @@
 // Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
 
-const { File, log, decode, encode } = Tera;
+const { File, fs, log, decode, encode } = Tera;
 
 async function main() {
   // Read and write to the same file.
@@ -16,6 +16,21 @@ async function main() {
   const writeContent = `This is a random value from Tera Js: ${Math.random()}\n`;
 
   await file.writeAll(encode(writeContent));
+
+  await file.sync();
+
+  log.info(">> file size after write =", (await file.stat()).size);
+
+  file.close();
+
+  // Inspect the file and its dir.
+  const info = await fs.stat("/examples/txt/files.txt");
+
+  log.info(">> file size =", info.size, ", modified =", info.modified);
+
+  for (const entry of await fs.readDir("/examples/txt")) {
+    log.info(">> dir entry =", entry.name);
+  }
 }
 
 if (import.meta.main) {
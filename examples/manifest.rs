@@ -0,0 +1,57 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+extern crate tera;
+
+use deno_core::serde_json;
+use tera::{
+    permissions::{PermissionRegistry, Permissions, PermissionsManifest},
+    Runtime,
+};
+use tokio::fs;
+use utilities::result::{Context, Result};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Read manifest. Any serde format works.
+    let manifest = format!(
+        r#"{{
+            "fs_root": "{}",
+            "permissions": {{
+                "Fs": {{ "Execute": ["/examples/js/**"] }}
+            }}
+        }}"#,
+        env!("CARGO_MANIFEST_DIR")
+    );
+
+    let manifest: PermissionsManifest =
+        serde_json::from_str(&manifest).context("parsing manifest")?;
+
+    // Create permissions
+    let registry = PermissionRegistry::default();
+    let permissions = Permissions::from_manifest(&manifest, &registry)?;
+
+    // Permissions can be serialized back.
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&permissions.to_manifest(&registry)?)
+            .context("serializing manifest")?
+    );
+
+    // Create a new runtime.
+    let mut runtime = Runtime::with_permissions(
+        permissions,
+        false,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+    .await?;
+
+    // Get main module code.
+    let main_module_code = fs::read_to_string("examples/js/modules.js").await?;
+
+    // Execute main module.
+    runtime
+        .execute_module("/examples/js/modules.js", main_module_code)
+        .await
+}
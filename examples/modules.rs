@@ -26,8 +26,14 @@ async fn main() -> Result<()> {
         .build();
 
     // Create a new runtime.
-    let mut runtime =
-        Runtime::with_permissions(permissions, false, vec![], Default::default()).await?;
+    let mut runtime = Runtime::with_permissions(
+        permissions,
+        false,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+    .await?;
 
     // Get main module code.
     let main_module_code = fs::read_to_string("examples/js/modules.js").await?;
@@ -2,14 +2,14 @@
 
 extern crate tera;
 
-use std::{convert::TryFrom, path::PathBuf};
+use std::convert::TryFrom;
 
 use tera::{
     permissions::{
         fs::{Fs, FsPath, FsRoot},
         Permissions,
     },
-    Runtime,
+    Postscripts, Runtime,
 };
 use tokio::fs;
 use utilities::result::Result;
@@ -29,7 +29,8 @@ async fn main() -> Result<()> {
     let mut runtime = Runtime::with_permissions(
         permissions,
         false,
-        vec![PathBuf::from("examples/js/30_globals.js")],
+        Postscripts::new().add("examples/js/30_globals.js"),
+        Default::default(),
         Default::default(),
     )
     .await?;
@@ -1,5 +1,29 @@
 // Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
 
+use std::{fmt, time::Duration};
+
 // Re-exports
 pub use deno_core::error::AnyError;
 pub use deno_core::error::JsError;
+
+/// The error returned when an execution is terminated for running past one of its deadlines.
+///
+/// Unlike a [`JsError`], it is never raised by the script itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutError {
+    WallTime(Duration),
+    CpuTime(Duration),
+}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WallTime(limit) => {
+                write!(f, "execution exceeded wall-clock deadline of {:?}", limit)
+            }
+            Self::CpuTime(limit) => write!(f, "execution exceeded cpu time budget of {:?}", limit),
+        }
+    }
+}
+
+impl std::error::Error for TimeoutError {}
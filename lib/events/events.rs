@@ -1,19 +1,77 @@
 // Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
 
-use std::pin::Pin;
+use std::{collections::HashMap, pin::Pin};
 
 use super::HttpEvent;
 use futures_util::Future;
 use utilities::{
+    errors,
     hyper::{Body, Response},
     result::Result,
 };
 
+/// Identifies an HTTP event dispatched to a long-lived runtime.
+pub type HttpEventId = u32;
+
 #[derive(Default)]
 pub struct Events {
-    pub http: Option<HttpEvent>,
+    pub http: Option<HttpEvent>, // The event the runtime is created with.
+    dispatched_http: HashMap<HttpEventId, HttpEvent>, // Events being handled by the registered listener.
+    next_http_event_id: HttpEventId,
 }
 
 pub trait EventResponder {
     fn send_response(&self, response: Response<Body>) -> Pin<Box<dyn Future<Output = Result<()>>>>;
 }
+
+impl Events {
+    pub fn with_http(event: HttpEvent) -> Self {
+        Self {
+            http: Some(event),
+            ..Default::default()
+        }
+    }
+
+    /// Gets a dispatched HTTP event or, if no ID is specified, the event the runtime is created with.
+    pub fn get_http(&self, id: Option<HttpEventId>) -> Option<&HttpEvent> {
+        match id {
+            Some(id) => self.dispatched_http.get(&id),
+            None => self.http.as_ref(),
+        }
+    }
+
+    pub fn get_http_mut(&mut self, id: Option<HttpEventId>) -> Option<&mut HttpEvent> {
+        match id {
+            Some(id) => self.dispatched_http.get_mut(&id),
+            None => self.http.as_mut(),
+        }
+    }
+
+    /// Gets the number of dispatched HTTP events that are still being handled.
+    pub fn pending_http_count(&self) -> usize {
+        self.dispatched_http.len()
+    }
+
+    /// Adds an event for the registered listener to handle. IDs of events still being handled are never reused.
+    pub(crate) fn add_dispatched_http(&mut self, event: HttpEvent) -> Result<HttpEventId> {
+        // SEC: Reusing an ID would hand one request's event to another.
+        if self.dispatched_http.len() as u64 > HttpEventId::MAX as u64 {
+            return errors::limit_exceeded_error_t("too many HTTP events being handled");
+        }
+
+        // IDs wrap around, so skip those still in use.
+        let mut id = self.next_http_event_id;
+        while self.dispatched_http.contains_key(&id) {
+            id = id.wrapping_add(1);
+        }
+
+        self.next_http_event_id = id.wrapping_add(1);
+        self.dispatched_http.insert(id, event);
+
+        Ok(id)
+    }
+
+    pub(crate) fn remove_dispatched_http(&mut self, id: HttpEventId) -> Option<HttpEvent> {
+        self.dispatched_http.remove(&id)
+    }
+}
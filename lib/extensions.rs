@@ -1,16 +1,22 @@
 // Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
 
 mod cache;
+mod custom;
 mod env;
 mod event_http;
 mod fs;
+mod permissions;
 mod crypto;
 
 pub use cache::cache;
+pub use custom::*;
 pub use env::env;
 pub use event_http::event_http;
-pub use fs::fs;
+pub use fs::{fs, LeakedFile};
+pub use permissions::permissions;
 pub use crypto::crypto;
 
+pub(crate) use fs::close_leaked_files;
+
 // Re-export
 pub use deno_core::*;
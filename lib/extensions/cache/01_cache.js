@@ -9,7 +9,7 @@
     return core.opSync("opCacheGet", key);
   }
 
-  window.__bootstrap.httpEvent = {
+  window.__bootstrap.cache = {
     cacheGet,
   };
 })(globalThis);
@@ -1,8 +1,9 @@
 // Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
 //! No support for non-ascii headers yet.
 
+use crate::include_js_files;
 use crate::permissions::Permissions;
-use deno_core::{error::AnyError, include_js_files, op_sync, Extension, OpState};
+use deno_core::{error::AnyError, op_sync, Extension, OpState};
 use std::cell::RefCell;
 use std::rc::Rc;
 
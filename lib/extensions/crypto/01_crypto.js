@@ -9,7 +9,7 @@
     return core.opSync("opCryptoCreateHmac", key);
   }
 
-  window.__bootstrap.httpEvent = {
+  window.__bootstrap.crypto = {
     cryptoCreateHmac,
   };
 })(globalThis);
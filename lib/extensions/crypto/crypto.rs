@@ -1,8 +1,9 @@
 // Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
 //! No support for non-ascii headers yet.
 
+use crate::include_js_files;
 use crate::permissions::Permissions;
-use deno_core::{error::AnyError, include_js_files, op_sync, Extension, OpState};
+use deno_core::{error::AnyError, op_sync, Extension, OpState};
 use std::cell::RefCell;
 use std::rc::Rc;
 
@@ -10,7 +11,7 @@ pub fn crypto(permissions: Rc<RefCell<Permissions>>) -> Extension {
     let extension = Extension::builder()
         .js(include_js_files!(
             prefix "(tera:extensions) ",
-            "lib/extensions/cache/01_crypto.js",
+            "lib/extensions/crypto/01_crypto.js",
         ))
         .ops(vec![("opCryptoCreateHmac", op_sync(op_crypto_create_hmac))])
         .state(move |state| {
@@ -0,0 +1,220 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+//! Helpers for writing permission-checked extensions outside of Tera.
+
+use crate::{
+    permissions::{PermissionType, Permissions, Resource},
+    JsFile,
+};
+use deno_core::parking_lot::Mutex;
+use deno_core::{error::AnyError, op_async, op_sync, serde_json, Extension, OpFn, OpState};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{cell::RefCell, collections::HashMap, future::Future, rc::Rc};
+use utilities::result;
+
+/// The permission an op requires. It is checked before the op body runs.
+pub trait OpPermission<A, B>: 'static {
+    fn check(&self, permissions: &Permissions, arg_a: &A, arg_b: &B) -> result::Result<()>;
+}
+
+/// Requires a permission type to be granted regardless of the op arguments.
+pub struct Requires<P>(P);
+
+/// Requires the resource derived from the op arguments to be in the allow list of a permission type.
+pub struct RequiresResource<P, F>(P, F);
+
+/// An extension whose ops check their permissions and whose JS namespace is exposed on `Tera`.
+///
+/// ```ignore
+/// CustomExtension::new("kv", permissions)
+///     .js(include_js_files!(prefix "(kv) ", "js/01_kv.js",))
+///     .op_sync("opKvGet", requires(Kv::Read), op_kv_get)
+///     .build()
+/// ```
+///
+/// The JS files are expected to set `window.__bootstrap.<namespace>`, which then becomes `Tera.<namespace>`.
+pub struct CustomExtension {
+    namespace: &'static str,
+    permissions: Rc<RefCell<Permissions>>,
+    js_files: Vec<JsFile>,
+    ops: Vec<(&'static str, Box<OpFn>)>,
+}
+
+impl CustomExtension {
+    pub fn new(namespace: &'static str, permissions: Rc<RefCell<Permissions>>) -> Self {
+        Self {
+            namespace,
+            permissions,
+            js_files: vec![],
+            ops: vec![],
+        }
+    }
+
+    pub fn js(mut self, js_files: Vec<JsFile>) -> Self {
+        self.js_files.extend(js_files);
+        self
+    }
+
+    pub fn op_sync<A, B, R>(
+        mut self,
+        name: &'static str,
+        permission: impl OpPermission<A, B>,
+        op: impl Fn(&mut OpState, A, B) -> Result<R, AnyError> + 'static,
+    ) -> Self
+    where
+        A: DeserializeOwned,
+        B: DeserializeOwned,
+        R: Serialize + 'static,
+    {
+        let op = op_sync(move |state: &mut OpState, arg_a: A, arg_b: B| {
+            {
+                let permissions = state.borrow::<Rc<RefCell<Permissions>>>().borrow();
+                permission.check(&permissions, &arg_a, &arg_b)?;
+            }
+
+            op(state, arg_a, arg_b)
+        });
+
+        self.ops.push((name, op));
+        self
+    }
+
+    pub fn op_async<A, B, R, F>(
+        mut self,
+        name: &'static str,
+        permission: impl OpPermission<A, B>,
+        op: impl Fn(Rc<RefCell<OpState>>, A, B) -> F + 'static,
+    ) -> Self
+    where
+        A: DeserializeOwned + 'static,
+        B: DeserializeOwned + 'static,
+        R: Serialize + 'static,
+        F: Future<Output = Result<R, AnyError>> + 'static,
+    {
+        let permission = Rc::new(permission);
+        let op = Rc::new(op);
+
+        let op = op_async(move |state: Rc<RefCell<OpState>>, arg_a: A, arg_b: B| {
+            let permission = Rc::clone(&permission);
+            let op = Rc::clone(&op);
+
+            async move {
+                {
+                    let permissions_rc =
+                        Rc::clone(state.borrow().borrow::<Rc<RefCell<Permissions>>>());
+                    permission.check(&permissions_rc.borrow(), &arg_a, &arg_b)?;
+                }
+
+                op(state, arg_a, arg_b).await
+            }
+        });
+
+        self.ops.push((name, op));
+        self
+    }
+
+    pub fn build(self) -> Extension {
+        let permissions = self.permissions;
+
+        // Register the namespace under `__custom` after the extension's own JS files have set it.
+        let mut js_files = self.js_files;
+        let script = get_register_script(self.namespace);
+        js_files.push((
+            get_register_script_name(self.namespace),
+            Box::new(move || Ok(script.clone())),
+        ));
+
+        Extension::builder()
+            .js(js_files)
+            .ops(self.ops)
+            .state(move |state| {
+                if !state.has::<Rc<RefCell<Permissions>>>() {
+                    state.put(Rc::clone(&permissions));
+                }
+
+                Ok(())
+            })
+            .build()
+    }
+}
+
+/// Requires a permission type to be granted.
+pub fn requires<P>(permission: P) -> Requires<P> {
+    Requires(permission)
+}
+
+/// Requires the resource returned by `get_resource` to be allowed for a permission type.
+pub fn requires_resource<P, F>(permission: P, get_resource: F) -> RequiresResource<P, F> {
+    RequiresResource(permission, get_resource)
+}
+
+impl<A, B, P> OpPermission<A, B> for Requires<P>
+where
+    P: Into<Box<dyn PermissionType>> + Clone + 'static,
+{
+    fn check(&self, permissions: &Permissions, _: &A, _: &B) -> result::Result<()> {
+        permissions.check_exists(self.0.clone())
+    }
+}
+
+impl<A, B, P, F, R> OpPermission<A, B> for RequiresResource<P, F>
+where
+    P: Into<Box<dyn PermissionType>> + Clone + 'static,
+    F: Fn(&A, &B) -> R + 'static,
+    R: Into<Box<dyn Resource>>,
+{
+    fn check(&self, permissions: &Permissions, arg_a: &A, arg_b: &B) -> result::Result<()> {
+        permissions.check(self.0.clone(), (self.1)(arg_a, arg_b))
+    }
+}
+
+/// Custom checks that need more than a single permission type.
+impl<A, B, F> OpPermission<A, B> for F
+where
+    F: Fn(&Permissions, &A, &B) -> result::Result<()> + 'static,
+{
+    fn check(&self, permissions: &Permissions, arg_a: &A, arg_b: &B) -> result::Result<()> {
+        self(permissions, arg_a, arg_b)
+    }
+}
+
+/// Names the register script after the namespace so that errors and stack traces tell extensions apart.
+fn get_register_script_name(namespace: &'static str) -> &'static str {
+    // Script names are static in deno. Each name is leaked once and reused by later builds.
+    *REGISTER_SCRIPT_NAMES
+        .lock()
+        .entry(namespace)
+        .or_insert_with(|| {
+            let name = format!(
+                "(tera:extensions) [register custom namespace {:?}]",
+                namespace
+            );
+            Box::leak(name.into_boxed_str())
+        })
+}
+
+fn get_register_script(namespace: &str) -> String {
+    // SEC: Namespace is JSON-encoded so that it can't break out of the string.
+    let namespace = serde_json::to_string(namespace).unwrap_or_default();
+
+    format!(
+        r#""use strict";
+
+((window) => {{
+  const namespace = {namespace};
+  const custom = (window.__bootstrap.__custom ??= {{}});
+
+  if (namespace in custom) {{
+    throw new Error(`custom namespace "${{namespace}}" is already registered`);
+  }}
+
+  custom[namespace] = window.__bootstrap[namespace];
+}})(globalThis);
+"#,
+        namespace = namespace
+    )
+}
+
+lazy_static! {
+    static ref REGISTER_SCRIPT_NAMES: Mutex<HashMap<&'static str, &'static str>> =
+        Mutex::new(HashMap::new());
+}
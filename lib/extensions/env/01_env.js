@@ -9,7 +9,7 @@
     return core.opSync("opEnvGet", key);
   }
 
-  window.__bootstrap.httpEvent = {
+  window.__bootstrap.env = {
     envGet,
   };
 })(globalThis);
@@ -5,80 +5,86 @@
 ((window) => {
   const { core } = window.__bootstrap;
 
-  function httpGetRequestHeaders(key) {
-    return core.opSync("opEvGetRequestHeaders", key);
+  // An event ID of null refers to the event the runtime is created with.
+
+  function httpGetRequestHeaders(eventId) {
+    return core.opSync("opEvGetRequestHeaders", eventId);
   }
 
-  function httpGetRequestHeader(key) {
-    return core.opSync("opEvGetRequestHeader", key);
+  function httpGetRequestHeader(eventId, key) {
+    return core.opSync("opEvGetRequestHeader", eventId, key);
   }
 
-  function httpSetRequestHeader(key, value) {
-    return core.opSync("opEvSetRequestHeader", key, value);
+  function httpSetRequestHeader(eventId, key, value) {
+    return core.opSync("opEvSetRequestHeader", eventId, [key, value]);
   }
 
-  function httpGetRequestUriScheme() {
-    return core.opSync("opEvGetRequestUriScheme");
+  function httpGetRequestUriScheme(eventId) {
+    return core.opSync("opEvGetRequestUriScheme", eventId);
   }
 
-  function httpGetRequestUriAuthority() {
-    return core.opSync("opEvGetRequestUriAuthority");
+  function httpGetRequestUriAuthority(eventId) {
+    return core.opSync("opEvGetRequestUriAuthority", eventId);
   }
 
-  function httpGetRequestUriPath() {
-    return core.opSync("opEvGetRequestUriPath");
+  function httpGetRequestUriPath(eventId) {
+    return core.opSync("opEvGetRequestUriPath", eventId);
   }
 
-  function httpGetRequestUriQuery() {
-    return core.opSync("opEvGetRequestUriQuery");
+  function httpGetRequestUriQuery(eventId) {
+    return core.opSync("opEvGetRequestUriQuery", eventId);
   }
 
-  function httpGetRequestUriPathQuery() {
-    return core.opSync("opEvGetRequestUriPathQuery");
+  function httpGetRequestUriPathQuery(eventId) {
+    return core.opSync("opEvGetRequestUriPathQuery", eventId);
   }
 
-  function httpGetRequestUriHost() {
-    return core.opSync("opEvGetRequestUriHost");
+  function httpGetRequestUriHost(eventId) {
+    return core.opSync("opEvGetRequestUriHost", eventId);
   }
 
-  function httpGetRequestUriPort() {
-    return core.opSync("opEvGetRequestUriPort");
+  function httpGetRequestUriPort(eventId) {
+    return core.opSync("opEvGetRequestUriPort", eventId);
   }
 
-  function httpGetRequestMethod() {
-    return core.opSync("opEvGetRequestMethod");
+  function httpGetRequestMethod(eventId) {
+    return core.opSync("opEvGetRequestMethod", eventId);
   }
 
-  function httpGetRequestVersion() {
-    return core.opSync("opEvGetRequestVersion");
+  function httpGetRequestVersion(eventId) {
+    return core.opSync("opEvGetRequestVersion", eventId);
   }
 
-  function httpGetRequestBodyReadStream() {
-    return core.opSync("opEvGetRequestBodyReadStream");
+  function httpGetRequestBodyReadStream(eventId) {
+    return core.opSync("opEvGetRequestBodyReadStream", eventId);
   }
 
   async function httpReadRequestBodyChunk(rid, buffer) {
-    return core.opAsync("opEvReadRequestBodyChunk", rid, buffer);
+    return core.opAsync("opEvRequestReadBodyChunk", rid, buffer);
   }
 
-  function httpGetRequestBodySizeHint(buffer) {
-    return core.opAsync("opEvGetRequestBodySizeHint", buffer);
+  function httpGetRequestBodySizeHint(eventId) {
+    return core.opSync("opEvGetRequestBodySizeHint", eventId);
   }
 
-  function httpSetResponseParts(key, value) {
-    return core.opSync("opHttpSetResponseParts", key, value);
+  function httpSetResponseParts(eventId, parts) {
+    return core.opSync("opHttpSetResponseParts", eventId, parts);
   }
 
-  async function httpSetSendResponseBody(buf) {
-    return core.opAsync("opEvSetSendResponseBody", buf);
+  async function httpSetSendResponseBody(eventId, buf) {
+    return core.opAsync("opEvSetSendResponseBody", eventId, buf);
   }
 
-  async function httpSetSendResponseBodyWriteStream(buf) {
-    return core.opAsync("opEvSetSendResponseBodyWriteStream", buf);
+  async function httpSetSendResponseBodyWriteStream(eventId) {
+    return core.opAsync("opEvSetSendResponseBodyWriteStream", eventId);
   }
 
   async function httpWriteResponseBodyChunk(rid, buf) {
-    return core.opAsync("opEvWriteResponseBodyChunk", rid, buf);
+    return core.opAsync("opEvResponseWriteBodyChunk", rid, buf);
+  }
+
+  function httpEventDone(eventId) {
+    return core.opSync("opEvHttpEventDone", eventId);
   }
 
   window.__bootstrap.httpEvent = {
@@ -101,5 +107,6 @@
     httpGetRequestUriPathQuery,
     httpGetRequestUriHost,
     httpGetRequestUriPort,
+    httpEventDone,
   };
 })(globalThis);
@@ -1,11 +1,12 @@
 // Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
 //! No support for non-ascii headers yet.
 
-use crate::events::Events;
+use crate::events::{Events, HttpEventId};
+use crate::include_js_files;
 use crate::permissions::events::event_http::HttpEvent;
 use crate::permissions::Permissions;
 use deno_core::parking_lot::Mutex;
-use deno_core::{error::AnyError, include_js_files, op_async, Extension, OpState};
+use deno_core::{error::AnyError, op_async, Extension, OpState};
 use deno_core::{op_sync, Resource, ResourceId, ZeroCopyBuf};
 use futures_util::Stream;
 use serde::Deserialize;
@@ -101,6 +102,8 @@ pub fn event_http(permissions: Rc<RefCell<Permissions>>, events: Rc<RefCell<Even
                 "opEvResponseWriteBodyChunk",
                 op_async(op_http_write_response_body_chunk),
             ),
+            // Dispatch.
+            ("opEvHttpEventDone", op_sync(op_http_event_done)),
         ])
         .state(move |state| {
             if !state.has::<Rc<RefCell<Permissions>>>() {
@@ -198,14 +201,14 @@ impl Resource for BufferQueueResource {}
 
 fn op_http_get_request_headers(
     state: &mut OpState,
-    _: (),
+    event_id: Option<HttpEventId>,
     _: (),
 ) -> Result<HashMap<String, String>, AnyError> {
     let events_rc = Rc::clone(state.borrow::<Rc<RefCell<Events>>>());
     let events = events_rc.borrow();
 
     // Get request from event.
-    let request = match events.http.as_ref() {
+    let request = match events.get_http(event_id) {
         Some(event) => &event.request,
         None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
     };
@@ -227,14 +230,14 @@ fn op_http_get_request_headers(
 
 fn op_http_get_request_header(
     state: &mut OpState,
+    event_id: Option<HttpEventId>,
     key: String,
-    _: (),
 ) -> Result<Option<String>, AnyError> {
     let events_rc = Rc::clone(state.borrow::<Rc<RefCell<Events>>>());
     let events = events_rc.borrow();
 
     // Get request from event.
-    let request = match events.http.as_ref() {
+    let request = match events.get_http(event_id) {
         Some(event) => &event.request,
         None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
     };
@@ -254,14 +257,14 @@ fn op_http_get_request_header(
 
 fn op_http_set_request_header(
     state: &mut OpState,
-    key: String,
-    value: String,
+    event_id: Option<HttpEventId>,
+    (key, value): (String, String),
 ) -> Result<Option<String>, AnyError> {
     let events_rc = Rc::clone(state.borrow::<Rc<RefCell<Events>>>());
     let mut events = events_rc.borrow_mut();
 
     // Get request from event.
-    let request = match events.http.as_mut() {
+    let request = match events.get_http_mut(event_id) {
         Some(event) => &mut event.request,
         None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
     };
@@ -280,14 +283,14 @@ fn op_http_set_request_header(
 
 fn op_http_get_request_uri_scheme(
     state: &mut OpState,
-    _: (),
+    event_id: Option<HttpEventId>,
     _: (),
 ) -> Result<Option<String>, AnyError> {
     let events_rc = Rc::clone(state.borrow::<Rc<RefCell<Events>>>());
     let events = events_rc.borrow();
 
     // Get request from event.
-    let request = match events.http.as_ref() {
+    let request = match events.get_http(event_id) {
         Some(event) => &event.request,
         None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
     };
@@ -304,14 +307,14 @@ fn op_http_get_request_uri_scheme(
 
 fn op_http_get_request_uri_authority(
     state: &mut OpState,
-    _: (),
+    event_id: Option<HttpEventId>,
     _: (),
 ) -> Result<Option<String>, AnyError> {
     let events_rc = Rc::clone(state.borrow::<Rc<RefCell<Events>>>());
     let events = events_rc.borrow();
 
     // Get request from event.
-    let request = match events.http.as_ref() {
+    let request = match events.get_http(event_id) {
         Some(event) => &event.request,
         None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
     };
@@ -328,14 +331,14 @@ fn op_http_get_request_uri_authority(
 
 fn op_http_get_request_uri_query(
     state: &mut OpState,
-    _: (),
+    event_id: Option<HttpEventId>,
     _: (),
 ) -> Result<Option<String>, AnyError> {
     let events_rc = Rc::clone(state.borrow::<Rc<RefCell<Events>>>());
     let events = events_rc.borrow();
 
     // Get request from event.
-    let request = match events.http.as_ref() {
+    let request = match events.get_http(event_id) {
         Some(event) => &event.request,
         None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
     };
@@ -350,12 +353,16 @@ fn op_http_get_request_uri_query(
     Ok(query.map(|v| v.to_owned()))
 }
 
-fn op_http_get_request_uri_path(state: &mut OpState, _: (), _: ()) -> Result<String, AnyError> {
+fn op_http_get_request_uri_path(
+    state: &mut OpState,
+    event_id: Option<HttpEventId>,
+    _: (),
+) -> Result<String, AnyError> {
     let events_rc = Rc::clone(state.borrow::<Rc<RefCell<Events>>>());
     let events = events_rc.borrow();
 
     // Get request from event.
-    let request = match events.http.as_ref() {
+    let request = match events.get_http(event_id) {
         Some(event) => &event.request,
         None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
     };
@@ -372,14 +379,14 @@ fn op_http_get_request_uri_path(state: &mut OpState, _: (), _: ()) -> Result<Str
 
 fn op_http_get_request_uri_path_query(
     state: &mut OpState,
-    _: (),
+    event_id: Option<HttpEventId>,
     _: (),
 ) -> Result<Option<String>, AnyError> {
     let events_rc = Rc::clone(state.borrow::<Rc<RefCell<Events>>>());
     let events = events_rc.borrow();
 
     // Get request from event.
-    let request = match events.http.as_ref() {
+    let request = match events.get_http(event_id) {
         Some(event) => &event.request,
         None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
     };
@@ -396,14 +403,14 @@ fn op_http_get_request_uri_path_query(
 
 fn op_http_get_request_uri_host(
     state: &mut OpState,
-    _: (),
+    event_id: Option<HttpEventId>,
     _: (),
 ) -> Result<Option<String>, AnyError> {
     let events_rc = Rc::clone(state.borrow::<Rc<RefCell<Events>>>());
     let events = events_rc.borrow();
 
     // Get request from event.
-    let request = match events.http.as_ref() {
+    let request = match events.get_http(event_id) {
         Some(event) => &event.request,
         None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
     };
@@ -420,14 +427,14 @@ fn op_http_get_request_uri_host(
 
 fn op_http_get_request_uri_port(
     state: &mut OpState,
-    _: (),
+    event_id: Option<HttpEventId>,
     _: (),
 ) -> Result<Option<u16>, AnyError> {
     let events_rc = Rc::clone(state.borrow::<Rc<RefCell<Events>>>());
     let events = events_rc.borrow();
 
     // Get request from event.
-    let request = match events.http.as_ref() {
+    let request = match events.get_http(event_id) {
         Some(event) => &event.request,
         None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
     };
@@ -440,12 +447,16 @@ fn op_http_get_request_uri_port(
     Ok(request.uri().port_u16())
 }
 
-fn op_http_get_request_method(state: &mut OpState, _: (), _: ()) -> Result<String, AnyError> {
+fn op_http_get_request_method(
+    state: &mut OpState,
+    event_id: Option<HttpEventId>,
+    _: (),
+) -> Result<String, AnyError> {
     let events_rc = Rc::clone(state.borrow::<Rc<RefCell<Events>>>());
     let events = events_rc.borrow();
 
     // Get request from event.
-    let request = match events.http.as_ref() {
+    let request = match events.get_http(event_id) {
         Some(event) => &event.request,
         None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
     };
@@ -460,12 +471,16 @@ fn op_http_get_request_method(state: &mut OpState, _: (), _: ()) -> Result<Strin
     Ok(method)
 }
 
-fn op_http_get_request_version(state: &mut OpState, _: (), _: ()) -> Result<String, AnyError> {
+fn op_http_get_request_version(
+    state: &mut OpState,
+    event_id: Option<HttpEventId>,
+    _: (),
+) -> Result<String, AnyError> {
     let events_rc = Rc::clone(state.borrow::<Rc<RefCell<Events>>>());
     let events = events_rc.borrow();
 
     // Get request from event.
-    let request = match events.http.as_ref() {
+    let request = match events.get_http(event_id) {
         Some(event) => &event.request,
         None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
     };
@@ -480,12 +495,16 @@ fn op_http_get_request_version(state: &mut OpState, _: (), _: ()) -> Result<Stri
     Ok(version)
 }
 
-fn op_http_get_request_body_size_hint(state: &mut OpState, _: (), _: ()) -> Result<u64, AnyError> {
+fn op_http_get_request_body_size_hint(
+    state: &mut OpState,
+    event_id: Option<HttpEventId>,
+    _: (),
+) -> Result<u64, AnyError> {
     let events_rc = Rc::clone(state.borrow::<Rc<RefCell<Events>>>());
     let events = events_rc.borrow();
 
     // Get request from event.
-    let request = match events.http.as_ref() {
+    let request = match events.get_http(event_id) {
         Some(event) => &event.request,
         None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
     };
@@ -504,14 +523,14 @@ fn op_http_get_request_body_size_hint(state: &mut OpState, _: (), _: ()) -> Resu
 
 fn op_http_get_request_body_read_stream(
     state: &mut OpState,
-    _: (),
+    event_id: Option<HttpEventId>,
     _: (),
 ) -> Result<u32, AnyError> {
     let events_rc = Rc::clone(state.borrow::<Rc<RefCell<Events>>>());
     let mut events = events_rc.borrow_mut();
 
     // Get request from event.
-    let request = match events.http.as_mut() {
+    let request = match events.get_http_mut(event_id) {
         Some(event) => &mut event.request,
         None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
     };
@@ -555,14 +574,14 @@ async fn op_http_read_request_body_chunk(
 
 fn op_http_set_response_parts(
     state: &mut OpState,
+    event_id: Option<HttpEventId>,
     parts: ResponseParts,
-    _: (),
 ) -> Result<(), AnyError> {
     let events_rc = Rc::clone(state.borrow::<Rc<RefCell<Events>>>());
     let mut events = events_rc.borrow_mut();
 
     // Get objects from http.event.
-    let response = match events.http.as_mut() {
+    let response = match events.get_http_mut(event_id) {
         Some(event) => &mut event.response,
         None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
     };
@@ -603,17 +622,17 @@ fn op_http_set_response_parts(
 
 async fn op_http_set_send_response_body(
     state: Rc<RefCell<OpState>>,
+    event_id: Option<HttpEventId>,
     buf: ZeroCopyBuf,
-    _: (),
 ) -> Result<(), AnyError> {
     let events_rc = Rc::clone(state.borrow().borrow::<Rc<RefCell<Events>>>());
-    let mut events = events_rc.borrow_mut();
 
     // Get objects from http.event.
-    let (mut response, responder) = match events.http.as_mut() {
+    // The events are not borrowed while the response is sent so that other events can be handled in the meantime.
+    let (mut response, responder) = match events_rc.borrow_mut().get_http_mut(event_id) {
         Some(event) => (
             mem::take(&mut event.response), // Take ownership of response.
-            &mut event.responder,
+            Rc::clone(&event.responder),
         ),
         None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
     };
@@ -639,17 +658,17 @@ async fn op_http_set_send_response_body(
 // We are left with an eager streaming implementation that uses a queue.
 async fn op_http_set_send_response_body_write_stream(
     state: Rc<RefCell<OpState>>,
-    _: (),
+    event_id: Option<HttpEventId>,
     _: (),
 ) -> Result<u32, AnyError> {
     let events_rc = Rc::clone(state.borrow().borrow::<Rc<RefCell<Events>>>());
-    let mut events = events_rc.borrow_mut();
 
     // Get request from event.
-    let (mut response, responder) = match events.http.as_mut() {
+    // The events are not borrowed while the response is sent so that other events can be handled in the meantime.
+    let (mut response, responder) = match events_rc.borrow_mut().get_http_mut(event_id) {
         Some(event) => (
             mem::take(&mut event.response), // Take ownership of response.
-            &mut event.responder,
+            Rc::clone(&event.responder),
         ),
         None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
     };
@@ -712,3 +731,12 @@ async fn op_http_write_response_body_chunk(
 
     Ok(())
 }
+
+fn op_http_event_done(state: &mut OpState, event_id: HttpEventId, _: ()) -> Result<(), AnyError> {
+    let events_rc = Rc::clone(state.borrow::<Rc<RefCell<Events>>>());
+
+    // Drop the event once its handler has settled. This also drops its responder.
+    events_rc.borrow_mut().remove_dispatched_http(event_id);
+
+    Ok(())
+}
@@ -21,5 +21,65 @@
     return core.opAsync("opFsSeek", rid, buf);
   }
 
-  window.__bootstrap.fs = { fsOpen, fsRead, fsWrite, fsSeek };
+  function fsClose(rid) {
+    core.opSync("opFsClose", rid);
+  }
+
+  function fsTruncate(rid, len) {
+    return core.opAsync("opFsTruncate", rid, len);
+  }
+
+  function fsSync(rid) {
+    return core.opAsync("opFsSync", rid);
+  }
+
+  function fsDatasync(rid) {
+    return core.opAsync("opFsDatasync", rid);
+  }
+
+  function fsFstat(rid) {
+    return core.opAsync("opFsFstat", rid);
+  }
+
+  function fsStat(path) {
+    return core.opAsync("opFsStat", path);
+  }
+
+  function fsReadDir(path) {
+    return core.opAsync("opFsReadDir", path);
+  }
+
+  function fsMkdir(path, options) {
+    return core.opAsync("opFsMkdir", path, options);
+  }
+
+  function fsRemove(path, options) {
+    return core.opAsync("opFsRemove", path, options);
+  }
+
+  function fsRename(fromPath, toPath) {
+    return core.opAsync("opFsRename", fromPath, toPath);
+  }
+
+  function fsCopy(fromPath, toPath) {
+    return core.opAsync("opFsCopy", fromPath, toPath);
+  }
+
+  window.__bootstrap.fs = {
+    fsOpen,
+    fsRead,
+    fsWrite,
+    fsSeek,
+    fsClose,
+    fsTruncate,
+    fsSync,
+    fsDatasync,
+    fsFstat,
+    fsStat,
+    fsReadDir,
+    fsMkdir,
+    fsRemove,
+    fsRename,
+    fsCopy,
+  };
 })(globalThis);
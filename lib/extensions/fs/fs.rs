@@ -1,21 +1,26 @@
 // Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
 // TODO(appcypher): Synchronisation needed with fcntl. Also applies to db. https://blog.cloudflare.com/durable-objects-easy-fast-correct-choose-three/
 
-use deno_core::{
-    error::AnyError, include_js_files, op_async, Extension, OpState, Resource, ResourceId,
-};
+use deno_core::parking_lot::Mutex;
+use deno_core::{error::AnyError, op_async, op_sync, Extension, OpState, Resource, ResourceId};
 use deno_core::{AsyncRefCell, RcRef, ZeroCopyBuf};
-use serde::Deserialize;
+use log::warn;
+use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
-use std::io::SeekFrom;
-use std::path::PathBuf;
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use tokio::fs::{File, OpenOptions};
-use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use utilities::errors;
+use utilities::result::{Context, Result as SystemResult};
 
-use crate::permissions::fs::{Fs, FsPath, FsRoot};
-use crate::permissions::Permissions;
+use crate::include_js_files;
+use crate::permissions::fs::{Fs, FsOpenOptions, FsPath};
+use crate::permissions::{ManifestPermissionType, Permissions};
+use crate::vfs::{FsBackend, FsQuota, Vfs, VfsFile, VfsFileType, VfsMetadata};
 
 pub fn fs(permissions: Rc<RefCell<Permissions>>) -> Extension {
     let extension = Extension::builder()
@@ -28,12 +33,25 @@ pub fn fs(permissions: Rc<RefCell<Permissions>>) -> Extension {
             ("opFsWrite", op_async(op_fs_write)),
             ("opFsRead", op_async(op_fs_read)),
             ("opFsSeek", op_async(op_fs_seek)),
+            ("opFsClose", op_sync(op_fs_close)),
+            ("opFsTruncate", op_async(op_fs_truncate)),
+            ("opFsSync", op_async(op_fs_sync)),
+            ("opFsDatasync", op_async(op_fs_datasync)),
+            ("opFsFstat", op_async(op_fs_fstat)),
+            ("opFsStat", op_async(op_fs_stat)),
+            ("opFsReadDir", op_async(op_fs_read_dir)),
+            ("opFsMkdir", op_async(op_fs_mkdir)),
+            ("opFsRemove", op_async(op_fs_remove)),
+            ("opFsRename", op_async(op_fs_rename)),
+            ("opFsCopy", op_async(op_fs_copy)),
         ])
         .state(move |state| {
             if !state.has::<Rc<RefCell<Permissions>>>() {
                 state.put(Rc::clone(&permissions));
             }
 
+            state.put(OpenFiles::default());
+
             Ok(())
         })
         .build();
@@ -41,13 +59,28 @@ pub fn fs(permissions: Rc<RefCell<Permissions>>) -> Extension {
     extension
 }
 
+// The file is shared with the blocking threads that run file operations. Ops on a file still run one after the other.
+type SharedFile = Arc<Mutex<Box<dyn VfsFile>>>;
+
 #[derive(Debug)]
 struct FileResource {
-    file: AsyncRefCell<File>,
-    _path: String,
-    _options: FileOptions,
+    file: AsyncRefCell<SharedFile>,
+    backend: Arc<dyn Vfs>, // The backend the file was opened from. Used to measure disk usage for quotas.
+    path: String,
+    options: FileOptions,
 }
 
+/// A file that was still open when its module finished. It has been closed.
+#[derive(Debug, Clone)]
+pub struct LeakedFile {
+    pub rid: ResourceId,
+    pub path: String,
+}
+
+/// The files that have been opened but not closed yet.
+#[derive(Debug, Default)]
+struct OpenFiles(BTreeSet<ResourceId>);
+
 #[derive(Deserialize, Default, Debug)]
 struct FileOptions {
     write: bool,
@@ -59,12 +92,39 @@ struct FileOptions {
 
 impl Resource for FileResource {}
 
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct FileInfo {
+    is_file: bool,
+    is_directory: bool,
+    size: u64,
+    readonly: bool,
+    modified: Option<f64>, // Milliseconds since the Unix epoch, if supported by the backend.
+    accessed: Option<f64>,
+    created: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DirEntry {
+    name: String,
+    is_file: bool,
+    is_directory: bool,
+    is_symlink: bool,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+struct RecursiveOptions {
+    recursive: bool,
+}
+
 async fn op_fs_open(
     state: Rc<RefCell<OpState>>,
     abs_path_str: String,
     options: FileOptions,
 ) -> Result<ResourceId, AnyError> {
-    let abs_path = &PathBuf::from(&abs_path_str);
+    let abs_path = PathBuf::from(&abs_path_str);
     if !abs_path.starts_with(std::path::MAIN_SEPARATOR.to_string()) {
         return errors::new_error_t(format!(
             r#"expected specified path to be an absolute path starting with a path separator, {:?}"#,
@@ -72,57 +132,65 @@ async fn op_fs_open(
         ));
     }
 
-    let clean_full_path = {
+    let backend = {
         // We use OS-supported permissions for files. Permissions are added on file open/creation.
         let permissions_rc = Rc::clone(state.borrow().borrow::<Rc<RefCell<Permissions>>>());
         let permissions = permissions_rc.borrow();
 
         // Check create permission.
         if options.create {
-            permissions.check(Fs::Create, FsPath::from(abs_path))?;
+            permissions.check(Fs::Create, FsPath::from(&abs_path))?;
         }
 
         // Check open permission.
-        permissions.check(Fs::Open, FsPath::from(abs_path))?;
+        permissions.check(Fs::Open, FsPath::from(&abs_path))?;
 
         // Check read permission.
         if options.read {
-            permissions.check(Fs::Read, FsPath::from(abs_path))?;
+            permissions.check(Fs::Read, FsPath::from(&abs_path))?;
         }
 
         // Check write permission for write, append, and truncate.
         if options.write || options.truncate || options.append {
-            permissions.check(Fs::Write, FsPath::from(abs_path))?;
+            permissions.check(Fs::Write, FsPath::from(&abs_path))?;
         }
 
-        // Get root path from permissions.
-        let root = if let Some(state) = &permissions.state {
-            state.downcast_ref::<FsRoot>().unwrap().as_ref()
-        } else {
-            return errors::permission_error_t("root path not specified");
-        };
+        // Get backend from permissions.
+        FsBackend::from_state(&permissions.state)?
+    };
 
-        // The full path.
-        &Fs::clean_path(root, &PathBuf::from(abs_path))?
+    let open_options = FsOpenOptions {
+        read: options.read,
+        write: options.write,
+        append: options.append,
+        create: options.create,
+        truncate: options.truncate,
     };
 
-    // Open file with options specified.
-    let file = OpenOptions::new()
-        .read(options.read)
-        .write(options.write)
-        .append(options.append)
-        .truncate(options.truncate)
-        .create(options.create)
-        .open(clean_full_path)
-        .await?;
+    let quota = get_quota(&state);
+
+    // SEC: Open file with options specified. The backend keeps it inside its tree, even through symlinks.
+    let file = {
+        let backend = Arc::clone(&backend);
+        run_blocking(move || match quota {
+            Some(quota) => open_with_quota(&*backend, &abs_path, &open_options, &quota),
+            None => backend.open(&abs_path, &open_options),
+        })
+        .await?
+    };
 
     // Save file info for later.
-    let rid = state.borrow_mut().resource_table.add(FileResource {
-        file: AsyncRefCell::new(file),
-        _path: abs_path_str,
-        _options: options,
+    let mut state = state.borrow_mut();
+    let rid = state.resource_table.add(FileResource {
+        file: AsyncRefCell::new(Arc::new(Mutex::new(file))),
+        backend,
+        path: abs_path_str,
+        options,
     });
 
+    // Track the file so that it can be closed if the script forgets to.
+    state.borrow_mut::<OpenFiles>().0.insert(rid);
+
     Ok(rid)
 }
 
@@ -132,16 +200,39 @@ async fn op_fs_write(
     buf: ZeroCopyBuf,
 ) -> Result<usize, AnyError> {
     // SEC: No permission check because each file is opened with OS-supported perms.
-    let resource = state.borrow().resource_table.get::<FileResource>(rid)?;
+    let content = buf.to_vec();
 
-    let mut file_rc = RcRef::map(&resource, |f| &f.file).borrow_mut().await;
-    let file = file_rc.as_mut();
+    let quota = get_quota(&state);
+    let (backend, append) = get_file_backend(&state, rid)?;
 
-    // Write to file.
-    let total_written = file.write(&buf).await?;
+    let total_written = with_file(&state, rid, move |file| {
+        let quota = match &quota {
+            Some(quota) => quota,
+            None => return write_and_flush(file, &content),
+        };
 
-    // Flush to move intermediate buffered content to file.
-    file.flush().await?;
+        // Count the write against the quota.
+        let len = content.len() as u64;
+        let growth = get_growth(file, len, append)?;
+        quota.reserve_bytes_on_disk(&*backend, growth)?;
+        if let Err(error) = quota.reserve_bytes_written(len) {
+            quota.release_bytes_on_disk(growth);
+            return Err(error);
+        }
+
+        let result = write_and_flush(file, &content);
+
+        // Give back what was counted but not written. Nothing is counted on error.
+        let total_written = result
+            .as_ref()
+            .map_or(0, |total_written| *total_written as u64);
+        let unwritten = len.saturating_sub(total_written);
+        quota.release_bytes_written(unwritten);
+        quota.release_bytes_on_disk(unwritten.min(growth));
+
+        result
+    })
+    .await?;
 
     Ok(total_written)
 }
@@ -152,13 +243,16 @@ async fn op_fs_read(
     mut buf: ZeroCopyBuf,
 ) -> Result<usize, AnyError> {
     // SEC: No permission check because each file is opened with OS-supported perms.
-    let resource = state.borrow().resource_table.get::<FileResource>(rid)?;
-
-    let mut file_rc = RcRef::map(&resource, |f| &f.file).borrow_mut().await;
-    let file = file_rc.as_mut();
+    let mut content = vec![0; buf.len()];
 
     // Read from file.
-    let total_read = file.read(&mut buf).await?;
+    let (content, total_read) = with_file(&state, rid, move |file| {
+        let total_read = file.read(&mut content).context("reading from file")?;
+        Ok((content, total_read))
+    })
+    .await?;
+
+    buf[..total_read].copy_from_slice(&content[..total_read]);
 
     Ok(total_read)
 }
@@ -175,22 +269,516 @@ async fn op_fs_seek(
     args: SeekArgs,
 ) -> Result<u64, AnyError> {
     // SEC: No permission check because each file is opened with OS-supported perms.
-    let resource = state.borrow().resource_table.get::<FileResource>(rid)?;
-
-    let mut file_rc = RcRef::map(&resource, |f| &f.file).borrow_mut().await;
-    let file = file_rc.as_mut();
-
     let seek = {
         match args.whence {
-            0 => SeekFrom::Start(args.offset as u64),
-            1 => SeekFrom::Start(args.offset as u64),
-            2 => SeekFrom::Start(args.offset as u64),
+            0 => match u64::try_from(args.offset) {
+                Ok(offset) => SeekFrom::Start(offset),
+                Err(_) => {
+                    return errors::type_error_t(format!(
+                        r#"invalid offset "{}" from the start of the file"#,
+                        args.offset
+                    ))
+                }
+            },
+            1 => SeekFrom::Current(args.offset),
+            2 => SeekFrom::End(args.offset),
             _ => return errors::type_error_t(format!(r#"invalid whence value "{}""#, args.whence)),
         }
     };
 
     // Seek file.
-    let pos = file.seek(seek).await?;
+    let pos = with_file(&state, rid, move |file| {
+        file.seek(seek).context("seeking file")
+    })
+    .await?;
 
     Ok(pos)
 }
+
+fn op_fs_close(state: &mut OpState, rid: ResourceId, _: ()) -> Result<(), AnyError> {
+    // The file is closed once pending ops on it are done.
+    state.resource_table.take::<FileResource>(rid)?;
+    state.borrow_mut::<OpenFiles>().0.remove(&rid);
+
+    Ok(())
+}
+
+async fn op_fs_truncate(
+    state: Rc<RefCell<OpState>>,
+    rid: ResourceId,
+    len: u64,
+) -> Result<(), AnyError> {
+    // SEC: No permission check because each file is opened with OS-supported perms.
+    let quota = get_quota(&state);
+    let (backend, _) = get_file_backend(&state, rid)?;
+
+    with_file(&state, rid, move |file| {
+        let quota = match &quota {
+            Some(quota) => quota,
+            None => return file.set_len(len),
+        };
+
+        // Count the size change against the quota.
+        let file_len = file.metadata()?.len;
+        if len > file_len {
+            quota.reserve_bytes_on_disk(&*backend, len - file_len)?;
+        }
+
+        // Truncate or extend file.
+        file.set_len(len)?;
+
+        if len < file_len {
+            quota.release_bytes_on_disk(file_len - len);
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+async fn op_fs_sync(state: Rc<RefCell<OpState>>, rid: ResourceId, _: ()) -> Result<(), AnyError> {
+    // Sync content and metadata to disk.
+    with_file(&state, rid, |file| file.sync_all()).await
+}
+
+async fn op_fs_datasync(
+    state: Rc<RefCell<OpState>>,
+    rid: ResourceId,
+    _: (),
+) -> Result<(), AnyError> {
+    // Sync content, and only the metadata needed to read it back, to disk.
+    with_file(&state, rid, |file| file.sync_data()).await
+}
+
+async fn op_fs_fstat(
+    state: Rc<RefCell<OpState>>,
+    rid: ResourceId,
+    _: (),
+) -> Result<FileInfo, AnyError> {
+    let metadata = with_file(&state, rid, |file| file.metadata()).await?;
+
+    Ok(FileInfo::from(&metadata))
+}
+
+async fn op_fs_stat(
+    state: Rc<RefCell<OpState>>,
+    abs_path_str: String,
+    _: (),
+) -> Result<FileInfo, AnyError> {
+    let (backend, path) = check_path(&state, &abs_path_str, &[Fs::Info])?;
+
+    let metadata = run_blocking(move || backend.metadata(&path, true)).await?;
+
+    Ok(FileInfo::from(&metadata))
+}
+
+async fn op_fs_read_dir(
+    state: Rc<RefCell<OpState>>,
+    abs_path_str: String,
+    _: (),
+) -> Result<Vec<DirEntry>, AnyError> {
+    let (backend, path) = check_path(&state, &abs_path_str, &[Fs::Info])?;
+
+    let entries = run_blocking(move || backend.read_dir(&path)).await?;
+
+    let entries = entries
+        .into_iter()
+        .map(|entry| DirEntry {
+            name: entry.name,
+            is_file: entry.file_type == VfsFileType::File,
+            is_directory: entry.file_type == VfsFileType::Dir,
+            is_symlink: entry.file_type == VfsFileType::Symlink,
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+async fn op_fs_mkdir(
+    state: Rc<RefCell<OpState>>,
+    abs_path_str: String,
+    options: RecursiveOptions,
+) -> Result<(), AnyError> {
+    let quota = get_quota(&state);
+
+    if !options.recursive {
+        let (backend, path) = check_path(&state, &abs_path_str, &[Fs::Create])?;
+        return run_blocking(move || create_dir_with_quota(&*backend, &path, &quota)).await;
+    }
+
+    // Each missing dir is created one after the other so that every one of them is checked and confined.
+    let abs_path = PathBuf::from(&abs_path_str);
+    let mut ancestors = abs_path.ancestors().collect::<Vec<_>>();
+    ancestors.reverse();
+
+    for ancestor in ancestors.into_iter().skip(1) {
+        let ancestor_str = ancestor.to_string_lossy();
+
+        // SEC: Probing a dir tells whether it exists, so it needs the same permission as `stat`.
+        let (backend, path) = check_path(&state, &ancestor_str, &[Fs::Info])?;
+        let exists = run_blocking(move || Ok(backend.metadata(&path, true).is_ok())).await?;
+        if exists {
+            continue;
+        }
+
+        let (backend, path) = check_path(&state, &ancestor_str, &[Fs::Create])?;
+        let quota = quota.clone();
+        run_blocking(move || create_dir_with_quota(&*backend, &path, &quota)).await?;
+    }
+
+    Ok(())
+}
+
+async fn op_fs_remove(
+    state: Rc<RefCell<OpState>>,
+    abs_path_str: String,
+    options: RecursiveOptions,
+) -> Result<(), AnyError> {
+    let (backend, path) = check_path(&state, &abs_path_str, &[Fs::Write])?;
+
+    // SEC: The root itself can't be removed.
+    if is_root(&abs_path_str)? {
+        return errors::permission_error_t("removing the root dir");
+    }
+
+    // SEC: A dir is removed with its content, so nothing under it may be denied.
+    if options.recursive {
+        check_nested(&state, &abs_path_str)?;
+    }
+
+    let quota = get_quota(&state);
+
+    // SEC: Symlinks are removed, not the files they point to.
+    run_blocking(move || {
+        let quota = match &quota {
+            Some(quota) => quota,
+            None => return backend.remove(&path, options.recursive),
+        };
+
+        // Give back the size of what is removed to the quota.
+        let len = backend.tree_len(&path)?;
+        backend.remove(&path, options.recursive)?;
+        quota.release_bytes_on_disk(len);
+
+        Ok(())
+    })
+    .await
+}
+
+async fn op_fs_rename(
+    state: Rc<RefCell<OpState>>,
+    from_path_str: String,
+    to_path_str: String,
+) -> Result<(), AnyError> {
+    // SEC: A move reads the source as well as writing it.
+    let (backend, from_path) = check_path(&state, &from_path_str, &[Fs::Read, Fs::Write])?;
+    let (_, to_path) = check_path(&state, &to_path_str, &[Fs::Create, Fs::Write])?;
+
+    // SEC: The root itself can't be moved.
+    if is_root(&from_path_str)? || is_root(&to_path_str)? {
+        return errors::permission_error_t("renaming the root dir");
+    }
+
+    // SEC: Nothing moved may be denied, or it could be read from its new path. A dir is moved with its content, so
+    // nothing under it may be denied after the move either.
+    check_nested(&state, &from_path_str)?;
+
+    let from_is_dir = {
+        let backend = Arc::clone(&backend);
+        let from_path = from_path.clone();
+        run_blocking(move || {
+            Ok(backend
+                .metadata(&from_path, false)
+                .map_or(true, |m| m.is_dir()))
+        })
+        .await?
+    };
+
+    if from_is_dir {
+        check_nested(&state, &to_path_str)?;
+    }
+
+    let quota = get_quota(&state);
+
+    // SEC: Symlinks are renamed, not the files they point to.
+    run_blocking(move || {
+        let quota = match &quota {
+            Some(quota) => quota,
+            None => return backend.rename(&from_path, &to_path),
+        };
+
+        // Give back the size of what the rename replaces to the quota.
+        let replaced_len = backend.tree_len(&to_path).unwrap_or(0);
+        backend.rename(&from_path, &to_path)?;
+
+        // SEC: Renaming a file onto itself, e.g. through a symlinked dir, replaces nothing and leaves it in place.
+        if backend.metadata(&from_path, false).is_err() {
+            quota.release_bytes_on_disk(replaced_len);
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+async fn op_fs_copy(
+    state: Rc<RefCell<OpState>>,
+    from_path_str: String,
+    to_path_str: String,
+) -> Result<u64, AnyError> {
+    let (backend, from_path) = check_path(&state, &from_path_str, &[Fs::Read])?;
+    let (_, to_path) = check_path(&state, &to_path_str, &[Fs::Create, Fs::Write])?;
+
+    let quota = get_quota(&state);
+
+    // Copy file content.
+    run_blocking(move || {
+        let quota = match &quota {
+            Some(quota) => quota,
+            None => return backend.copy(&from_path, &to_path),
+        };
+
+        // Count the copy against the quota.
+        let from_len = backend.metadata(&from_path, true)?.len;
+        let to_len = match backend.metadata(&to_path, true) {
+            Ok(metadata) => Some(metadata.len),
+            Err(_) => None,
+        };
+
+        let growth = from_len.saturating_sub(to_len.unwrap_or(0));
+        quota.reserve_bytes_on_disk(&*backend, growth)?;
+        if let Err(error) = quota.reserve_bytes_written(from_len) {
+            quota.release_bytes_on_disk(growth);
+            return Err(error);
+        }
+
+        if to_len.is_none() {
+            if let Err(error) = quota.reserve_file_created() {
+                quota.release_bytes_written(from_len);
+                quota.release_bytes_on_disk(growth);
+                return Err(error);
+            }
+        }
+
+        // Give back everything that was counted if the copy fails.
+        let total_copied = backend.copy(&from_path, &to_path).map_err(|error| {
+            if to_len.is_none() {
+                quota.release_file_created();
+            }
+
+            quota.release_bytes_written(from_len);
+            quota.release_bytes_on_disk(growth);
+
+            error
+        })?;
+
+        if let Some(to_len) = to_len {
+            quota.release_bytes_on_disk(to_len.saturating_sub(from_len));
+        }
+
+        Ok(total_copied)
+    })
+    .await
+}
+
+/// Checks the permissions of a path, then gets the backend it is in.
+fn check_path(
+    state: &Rc<RefCell<OpState>>,
+    abs_path_str: &str,
+    permission_types: &[Fs],
+) -> Result<(Arc<dyn Vfs>, PathBuf), AnyError> {
+    let abs_path = Path::new(abs_path_str);
+    if !abs_path.starts_with(std::path::MAIN_SEPARATOR.to_string()) {
+        return errors::new_error_t(format!(
+            r#"expected specified path to be an absolute path starting with a path separator, {:?}"#,
+            abs_path
+        ));
+    }
+
+    let permissions_rc = Rc::clone(state.borrow().borrow::<Rc<RefCell<Permissions>>>());
+    let permissions = permissions_rc.borrow();
+
+    for permission_type in permission_types.iter() {
+        permissions.check(*permission_type, FsPath::from(abs_path))?;
+    }
+
+    // SEC: The backend keeps the path inside its tree, even through symlinks.
+    let backend = FsBackend::from_state(&permissions.state)?;
+
+    Ok((backend, abs_path.to_owned()))
+}
+
+/// Checks that no deny entry may match a path under a dir.
+fn check_nested(state: &Rc<RefCell<OpState>>, abs_path_str: &str) -> Result<(), AnyError> {
+    let permissions_rc = Rc::clone(state.borrow().borrow::<Rc<RefCell<Permissions>>>());
+    let permissions = permissions_rc.borrow();
+
+    permissions.check_nested(&Fs::variants(), FsPath::from(abs_path_str))?;
+
+    Ok(())
+}
+
+/// Runs a backend operation on a blocking thread so that the event loop is not held up by slow storage.
+async fn run_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> SystemResult<T> + Send + 'static,
+) -> Result<T, AnyError> {
+    Ok(tokio::task::spawn_blocking(f).await??)
+}
+
+/// Runs an operation on an open file. Operations on the same file run in the order they are called.
+async fn with_file<T: Send + 'static>(
+    state: &Rc<RefCell<OpState>>,
+    rid: ResourceId,
+    f: impl FnOnce(&mut dyn VfsFile) -> SystemResult<T> + Send + 'static,
+) -> Result<T, AnyError> {
+    let resource = state.borrow().resource_table.get::<FileResource>(rid)?;
+
+    // Held until the operation is done.
+    let file_rc = RcRef::map(&resource, |f| &f.file).borrow_mut().await;
+    let file = Arc::clone(&file_rc);
+
+    run_blocking(move || f(file.lock().as_mut())).await
+}
+
+/// Gets the disk quota of the permissions, if there is one.
+fn get_quota(state: &Rc<RefCell<OpState>>) -> Option<FsQuota> {
+    let permissions_rc = Rc::clone(state.borrow().borrow::<Rc<RefCell<Permissions>>>());
+    let permissions = permissions_rc.borrow();
+
+    permissions.state.get::<FsQuota>().cloned()
+}
+
+/// Gets the backend an open file is from, and whether the file appends.
+fn get_file_backend(
+    state: &Rc<RefCell<OpState>>,
+    rid: ResourceId,
+) -> Result<(Arc<dyn Vfs>, bool), AnyError> {
+    let resource = state.borrow().resource_table.get::<FileResource>(rid)?;
+    Ok((Arc::clone(&resource.backend), resource.options.append))
+}
+
+/// Opens a file and counts the file created, and the content truncated, against the quota.
+fn open_with_quota(
+    backend: &dyn Vfs,
+    path: &Path,
+    options: &FsOpenOptions,
+    quota: &FsQuota,
+) -> SystemResult<Box<dyn VfsFile>> {
+    let existing_len = backend
+        .metadata(path, true)
+        .ok()
+        .map(|metadata| metadata.len);
+
+    let created = existing_len.is_none() && options.create;
+    if created {
+        quota.reserve_file_created()?;
+    }
+
+    let file = backend.open(path, options).map_err(|error| {
+        if created {
+            quota.release_file_created();
+        }
+
+        error
+    })?;
+
+    if let (Some(existing_len), true) = (existing_len, options.truncate) {
+        quota.release_bytes_on_disk(existing_len);
+    }
+
+    Ok(file)
+}
+
+/// Creates a dir and counts it against the quota.
+fn create_dir_with_quota(
+    backend: &dyn Vfs,
+    path: &Path,
+    quota: &Option<FsQuota>,
+) -> SystemResult<()> {
+    let quota = match quota {
+        Some(quota) => quota,
+        None => return backend.create_dir(path),
+    };
+
+    quota.reserve_file_created()?;
+
+    backend.create_dir(path).map_err(|error| {
+        quota.release_file_created();
+        error
+    })
+}
+
+fn write_and_flush(file: &mut dyn VfsFile, content: &[u8]) -> SystemResult<usize> {
+    // Write to file.
+    let total_written = file.write(content).context("writing to file")?;
+
+    // Flush to move intermediate buffered content to file.
+    file.flush().context("flushing file")?;
+
+    Ok(total_written)
+}
+
+/// Gets how much a file grows by when `len` bytes are written at its position.
+fn get_growth(file: &mut dyn VfsFile, len: u64, append: bool) -> SystemResult<u64> {
+    let file_len = file.metadata()?.len;
+
+    // Appends always write at the end.
+    let position = if append {
+        file_len
+    } else {
+        file.seek(SeekFrom::Current(0))
+            .context("getting position in file")?
+    };
+
+    Ok(position.saturating_add(len).saturating_sub(file_len))
+}
+
+fn is_root(abs_path_str: &str) -> Result<bool, AnyError> {
+    let root = Path::new(&std::path::MAIN_SEPARATOR.to_string()).to_owned();
+    let clean_path = Fs::clean_path(&root, Path::new(abs_path_str))?;
+
+    Ok(clean_path == root)
+}
+
+/// Closes the files left open by scripts and returns them so that the leak can be reported.
+pub(crate) fn close_leaked_files(state: &mut OpState) -> Vec<LeakedFile> {
+    // Nothing to close without the fs extension.
+    let rids = match state.try_borrow_mut::<OpenFiles>() {
+        Some(open_files) => std::mem::take(&mut open_files.0),
+        None => return vec![],
+    };
+
+    let mut leaked_files = vec![];
+    for rid in rids {
+        // The file is closed once pending ops on it are done.
+        if let Ok(resource) = state.resource_table.take::<FileResource>(rid) {
+            warn!("closing file {:?} left open by script", resource.path);
+
+            leaked_files.push(LeakedFile {
+                rid,
+                path: resource.path.clone(),
+            });
+        }
+    }
+
+    leaked_files
+}
+
+impl From<&VfsMetadata> for FileInfo {
+    fn from(metadata: &VfsMetadata) -> Self {
+        Self {
+            is_file: metadata.is_file(),
+            is_directory: metadata.is_dir(),
+            size: metadata.len,
+            readonly: metadata.readonly,
+            modified: to_millis(metadata.modified),
+            accessed: to_millis(metadata.accessed),
+            created: to_millis(metadata.created),
+        }
+    }
+}
+
+fn to_millis(time: Option<SystemTime>) -> Option<f64> {
+    let duration = time?.duration_since(UNIX_EPOCH).ok()?;
+    Some(duration.as_millis() as f64)
+}
@@ -0,0 +1,5 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+mod permissions;
+
+pub use permissions::*;
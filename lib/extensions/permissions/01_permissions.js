@@ -0,0 +1,16 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+"use strict";
+
+((window) => {
+  const { core } = window.__bootstrap;
+
+  // Checks if a permission like "Fs.Read" is granted, optionally for a resource like "/data/file.txt".
+  function query(permissionType, resource) {
+    return core.opSync("opPermissionsQuery", permissionType, resource ?? null);
+  }
+
+  window.__bootstrap.permissions = {
+    query,
+  };
+})(globalThis);
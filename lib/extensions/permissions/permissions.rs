@@ -0,0 +1,46 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use crate::include_js_files;
+use crate::permissions::{PermissionRegistry, Permissions};
+use deno_core::{error::AnyError, op_sync, Extension, OpState};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Lets scripts query their permissions. Permission types are named as in the registry's manifests.
+pub fn permissions(
+    permissions: Rc<RefCell<Permissions>>,
+    registry: Rc<PermissionRegistry>,
+) -> Extension {
+    let extension = Extension::builder()
+        .js(include_js_files!(
+            prefix "(tera:extensions) ",
+            "lib/extensions/permissions/01_permissions.js",
+        ))
+        .ops(vec![("opPermissionsQuery", op_sync(op_permissions_query))])
+        .state(move |state| {
+            if !state.has::<Rc<RefCell<Permissions>>>() {
+                state.put(Rc::clone(&permissions));
+            }
+
+            state.put(Rc::clone(&registry));
+
+            Ok(())
+        })
+        .build();
+
+    extension
+}
+
+fn op_permissions_query(
+    state: &mut OpState,
+    permission_type: String,
+    resource: Option<String>,
+) -> Result<bool, AnyError> {
+    let registry = state.borrow::<Rc<PermissionRegistry>>();
+    let (permission_type, resource) = registry.parse(&permission_type, resource.as_deref())?;
+
+    // SEC: Read-only. Nothing is denied, observed or learnt.
+    let permissions = state.borrow::<Rc<RefCell<Permissions>>>().borrow();
+
+    Ok(permissions.query(permission_type, resource))
+}
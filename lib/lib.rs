@@ -7,6 +7,7 @@ pub mod events;
 pub mod extensions;
 pub mod loaders;
 pub mod permissions;
+pub mod vfs;
 mod macros;
 mod runtime;
 
@@ -1,14 +1,17 @@
 // Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
 
 use log::debug;
-use std::{cell::RefCell, fs, path::PathBuf, pin::Pin, rc::Rc};
+use std::{cell::RefCell, io::Read, path::Path, pin::Pin, rc::Rc};
 use utilities::{errors, result::Context};
 
 use deno_core::{futures::FutureExt, ModuleLoader, ModuleSource};
 
-use crate::permissions::{
-    fs::{Fs, FsPath, FsRoot},
-    Permissions,
+use crate::{
+    permissions::{
+        fs::{Fs, FsOpenOptions, FsPath},
+        Permissions,
+    },
+    vfs::FsBackend,
 };
 
 pub struct ESMLoader {
@@ -68,21 +71,24 @@ impl ModuleLoader for ESMLoader {
                 // Check permissions.
                 permissions.check(Fs::Execute, FsPath::from(module_path))?;
 
-                // Get root path from permissions.
-                let root = if let Some(state) = &permissions.state {
-                    state.downcast_ref::<FsRoot>().unwrap().as_ref()
-                } else {
-                    return errors::permission_error_t("root path not specified");
-                };
+                // Get backend from permissions.
+                let backend = FsBackend::from_state(&permissions.state)?;
 
-                // The full path.
-                let full_path = Fs::clean_path(root, &PathBuf::from(module_path))?;
-
-                debug!("Module full path = {:?}", full_path);
+                // SEC: Open module. The backend keeps it inside its tree, even through symlinks.
+                let mut file = backend.open(
+                    Path::new(module_path),
+                    &FsOpenOptions {
+                        read: true,
+                        ..Default::default()
+                    },
+                )?;
 
                 // Fetch module source.
-                fs::read_to_string(full_path)
-                    .context(format!(r#"reading module code from "{}""#, module_path))?
+                let mut code = String::new();
+                file.read_to_string(&mut code)
+                    .context(format!(r#"reading module code from "{}""#, module_path))?;
+
+                code
             };
 
             let mod_src = ModuleSource {
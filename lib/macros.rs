@@ -1,3 +1,36 @@
 // Copyright 2022 the Gigamono authors. All rights reserved. GPL-3.0 License.
 
-pub use deno_core::include_js_files; // Re-export macro.
+use crate::errors::AnyError;
+use std::fs;
+
+/// A JS file name paired with the function that loads its content.
+pub type JsFile = (&'static str, Box<dyn Fn() -> Result<String, AnyError>>);
+
+/// Same as deno's `include_js_files!` except that the files are embedded in the binary instead of read from the source checkout at runtime.
+///
+/// With the `dev` feature enabled, the files are still read from disk so that changes show up without recompiling.
+#[macro_export]
+macro_rules! include_js_files {
+    (prefix $prefix:literal, $($file:literal,)+) => {
+        vec![
+            $((
+                concat!($prefix, "/", $file),
+                Box::new(|| {
+                    $crate::load_js_file(
+                        concat!(env!("CARGO_MANIFEST_DIR"), "/", $file),
+                        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $file)),
+                    )
+                }) as Box<dyn Fn() -> ::std::result::Result<String, $crate::errors::AnyError>>,
+            ),)+
+        ]
+    };
+}
+
+#[doc(hidden)]
+pub fn load_js_file(path: &str, embedded: &'static str) -> Result<String, AnyError> {
+    if cfg!(feature = "dev") {
+        return Ok(fs::read_to_string(path)?);
+    }
+
+    Ok(embedded.to_string())
+}
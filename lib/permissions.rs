@@ -1,7 +1,18 @@
 // Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
 
+pub mod env;
 pub mod events;
 pub mod fs;
+pub mod glob;
+mod algebra;
+mod audit;
+mod grant;
+mod learning;
+mod manifest;
 mod permissions;
 
+pub use audit::*;
+pub use grant::*;
+pub use learning::*;
+pub use manifest::*;
 pub use permissions::*;
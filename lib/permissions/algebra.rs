@@ -0,0 +1,93 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use super::{fs::Fs, permissions::PermissionMap, Permissions, StateMap};
+use std::sync::Arc;
+use utilities::{errors, result::Result};
+
+impl Permissions {
+    /// Combines two sets, e.g. platform grants with tenant grants.
+    ///
+    /// Allow lists are merged. SEC: Deny entries and grant conditions of both sets apply, so the union never allows what
+    /// either set denies. Sets with different fs roots can't be combined.
+    ///
+    /// State, observers and the learner of `self` are kept. State missing from `self` is taken from `other`.
+    pub fn union(&self, other: &Permissions) -> Result<Permissions> {
+        // SEC: The union of bounded sets can't be expressed as a single bounded set.
+        if !self.bounds.is_empty() || !other.bounds.is_empty() {
+            return errors::permission_error_t("taking the union of narrowed permissions");
+        }
+
+        // SEC: Fs patterns are compiled against a root, so they would match different paths under another root.
+        let roots = (Fs::get_root(&self.state), Fs::get_root(&other.state));
+        if let (Ok(root), Ok(other_root)) = roots {
+            if root != other_root {
+                return errors::permission_error_t(format!(
+                    "taking the union of permissions with different fs roots, {:?} and {:?}",
+                    root, other_root
+                ));
+            }
+        }
+
+        let mut permissions = self.clone();
+        merge_maps(&mut permissions.map, &other.map);
+        merge_maps(&mut permissions.deny_map, &other.deny_map);
+        permissions.state.fill(&other.state);
+
+        // SEC: Conditions on the grants of both sets apply.
+        for (key, grant) in other.grants.iter() {
+            permissions
+                .grants
+                .entry(key.clone())
+                .or_default()
+                .merge(grant);
+        }
+
+        Ok(permissions)
+    }
+
+    /// Narrows a set to what both sets allow, e.g. a parent set with the grants of a child script.
+    ///
+    /// SEC: The result never allows more than either set. `other` is kept as a bound and every check must also pass it.
+    ///
+    /// State, observers and the learner of `self` are kept. The bound is checked against its own state, and takes the
+    /// state it is missing from `self`, e.g. the fs root. Its fs paths added without a root are mapped to that root.
+    pub fn intersection(&self, other: &Permissions) -> Result<Permissions> {
+        let mut permissions = self.clone();
+
+        // Permission types missing from either set are dropped.
+        permissions.map.retain(|key, _| other.map.contains_key(key));
+
+        // The bound only decides. Checks are still observed and learnt once.
+        let mut bound = other.clone();
+        bound.observers.clear();
+        bound.learner = None;
+
+        bound.state.fill(&self.state);
+        map_to_root(&mut bound.map, &bound.state)?;
+        map_to_root(&mut bound.deny_map, &bound.state)?;
+
+        permissions.bounds.push(Arc::new(bound));
+        Ok(permissions)
+    }
+}
+
+fn merge_maps(map: &mut PermissionMap, other: &PermissionMap) {
+    for (key, other_list) in other.iter() {
+        match map.get_mut(key) {
+            Some(list) => {
+                *list = Arc::new(list.iter().chain(other_list.iter()).cloned().collect());
+            }
+            None => {
+                map.insert(key.clone(), Arc::clone(other_list));
+            }
+        }
+    }
+}
+
+fn map_to_root(map: &mut PermissionMap, state: &StateMap) -> Result<()> {
+    for list in map.values_mut() {
+        *list = Arc::new(Fs::map_to_root(list, state)?);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use super::Resource;
+use log::{info, warn};
+use std::{fmt::Debug, sync::Mutex};
+
+/// A permission check and its decision.
+#[derive(Debug, Clone)]
+pub struct PermissionCheck {
+    pub permission_type: String,
+    pub resource: Option<Box<dyn Resource>>, // Not set for checks that only need the permission type to exist.
+    pub allowed: bool,
+    pub allow_entry: Option<Box<dyn Resource>>, // The allow list entry that grants access, if known.
+    pub deny_entry: Option<Box<dyn Resource>>,  // The deny list entry that denies access.
+}
+
+/// Gets notified of every permission check. Added with `PermissionsBuilder::add_observer`.
+pub trait PermissionObserver: Debug + Send + Sync {
+    fn observe(&self, check: &PermissionCheck);
+}
+
+/// Logs permission checks. Denied checks are logged as warnings.
+#[derive(Debug, Default)]
+pub struct LogSink;
+
+/// Records permission checks in memory.
+#[derive(Debug, Default)]
+pub struct MemoryRecorder {
+    checks: Mutex<Vec<PermissionCheck>>,
+}
+
+impl MemoryRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn checks(&self) -> Vec<PermissionCheck> {
+        self.checks.lock().unwrap().clone()
+    }
+
+    pub fn clear(&self) {
+        self.checks.lock().unwrap().clear();
+    }
+}
+
+impl PermissionObserver for LogSink {
+    fn observe(&self, check: &PermissionCheck) {
+        if check.allowed {
+            info!(
+                "Permission allowed: type = {}, resource = {:?}, allow entry = {:?}",
+                check.permission_type, check.resource, check.allow_entry
+            );
+        } else {
+            warn!(
+                "Permission denied: type = {}, resource = {:?}, deny entry = {:?}",
+                check.permission_type, check.resource, check.deny_entry
+            );
+        }
+    }
+}
+
+impl PermissionObserver for MemoryRecorder {
+    fn observe(&self, check: &PermissionCheck) {
+        self.checks.lock().unwrap().push(check.clone());
+    }
+}
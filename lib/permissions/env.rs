@@ -1,9 +1,9 @@
 // Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
 
-use crate::permissions::{PermissionType, PermissionTypeKey};
+use crate::permissions::{ManifestPermissionType, PermissionType, PermissionTypeKey};
 use std::any::TypeId;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Copy, Clone)]
 pub enum Env {
     Read,
 }
@@ -12,7 +12,21 @@ impl PermissionType for Env {
     fn get_key<'a>(&self) -> PermissionTypeKey {
         PermissionTypeKey {
             type_id: TypeId::of::<Self>(),
-            variant: 0,
+            variant: *self as i32,
         }
     }
 }
+
+impl ManifestPermissionType for Env {
+    const SECTION: &'static str = "Env";
+
+    fn variants() -> Vec<Self> {
+        vec![Self::Read]
+    }
+}
+
+impl Into<Box<dyn PermissionType>> for Env {
+    fn into(self) -> Box<dyn PermissionType> {
+        Box::new(self)
+    }
+}
@@ -1,9 +1,9 @@
 // Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
 
-use crate::permissions::{PermissionType, PermissionTypeKey};
+use crate::permissions::{ManifestPermissionType, PermissionType, PermissionTypeKey};
 use std::any::TypeId;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Copy, Clone)]
 pub enum HttpEvent {
     RequestRead,
     RequestWrite,
@@ -15,11 +15,24 @@ impl PermissionType for HttpEvent {
     fn get_key<'a>(&self) -> PermissionTypeKey {
         PermissionTypeKey {
             type_id: TypeId::of::<Self>(),
-            variant: 0,
+            variant: *self as i32,
         }
     }
 }
 
+impl ManifestPermissionType for HttpEvent {
+    const SECTION: &'static str = "HttpEvent";
+
+    fn variants() -> Vec<Self> {
+        vec![
+            Self::RequestRead,
+            Self::RequestWrite,
+            Self::ResponseWrite,
+            Self::ResponseSend,
+        ]
+    }
+}
+
 impl Into<Box<dyn PermissionType>> for HttpEvent {
     fn into(self) -> Box<dyn PermissionType> {
         Box::new(self)
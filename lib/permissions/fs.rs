@@ -1,15 +1,22 @@
 // Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
 
-use super::{PermissionType, PermissionTypeKey, Resource, State};
+mod beneath;
+
+pub use beneath::*;
+
+use super::{
+    glob::{self, Glob},
+    ManifestPermissionType, PermissionType, PermissionTypeKey, Resource, State, StateMap,
+};
+use crate::vfs::MountFs;
 use log::debug;
 use path_clean::PathClean;
-use regex::Regex;
 use std::{
     any::TypeId,
     convert::TryFrom,
     fs,
     path::{Component, Path, PathBuf},
-    rc::Rc,
+    sync::Arc,
 };
 use utilities::{
     errors::{self, SystemError},
@@ -34,7 +41,7 @@ pub enum Fs {
 #[derive(Clone, Debug)]
 pub struct FsPath {
     path: PathBuf,
-    regex: Option<Regex>, // The regex representation of the path.
+    glob: Option<Glob>, // The compiled pattern of an allow list path.
 }
 
 /// An [`fs path`](struct@FsPath) can be a location relative to a root path. FsRoot can be used to represent the root path.
@@ -78,6 +85,93 @@ impl Fs {
 
         return Ok(clean_path);
     }
+
+    /// Gets the dir paths are checked under. With mounts, paths are checked as they are and the root is the virtual root.
+    pub(crate) fn get_root(state: &StateMap) -> Result<PathBuf> {
+        if state.get::<MountFs>().is_some() {
+            return Ok(PathBuf::from(std::path::MAIN_SEPARATOR.to_string()));
+        }
+
+        Ok(state.try_get::<FsRoot>()?.as_ref().to_owned())
+    }
+
+    /// Maps the paths of a list that were added without a root or mounts, e.g. to a set that narrows another.
+    pub(super) fn map_to_root(
+        list: &[Box<dyn Resource>],
+        state: &StateMap,
+    ) -> Result<Vec<Box<dyn Resource>>> {
+        list.iter()
+            .map(|resource| match resource.downcast_ref::<FsPath>() {
+                Some(fs_path) if fs_path.glob.is_none() => {
+                    Ok(Self::map_path(&Self::get_root(state)?, &fs_path.path)?.into())
+                }
+                _ => Ok(resource.clone()),
+            })
+            .collect()
+    }
+
+    /// Joins an allowed path with the root and compiles its pattern.
+    fn map_path(root: &Path, abs_path: &Path) -> Result<FsPath> {
+        // Clean path.
+        let clean_full_dir = Self::clean_path(root, abs_path)?;
+
+        debug!("Allowed path = {:?}", clean_full_dir);
+
+        // SEC: Convert paths to UTF-8 strings.
+        let root_string = to_utf8_string(root)?;
+        let path_string = to_utf8_string(&clean_full_dir)?;
+
+        // SEC: The root is matched literally so that glob syntax in its name has no effect.
+        // Supported patterns are documented in the glob module.
+        let pattern = match path_string.strip_prefix(&root_string) {
+            Some(pattern) => pattern,
+            None => {
+                return errors::new_error_t(format!(
+                    r#"expected "allow" path to be under the root dir, {:?}"#,
+                    clean_full_dir
+                ))
+            }
+        };
+
+        let glob = Glob::with_literal_prefix(&root_string, pattern)?;
+
+        Ok(FsPath {
+            path: clean_full_dir,
+            glob: Some(glob),
+        })
+    }
+
+    fn is_write(&self) -> bool {
+        matches!(self, Self::Create | Self::Write)
+    }
+
+    /// Gets the clean path of a resource as it is matched against patterns.
+    fn get_resource_string(abs_path: &Box<dyn Resource>, state: &StateMap) -> Result<String> {
+        // Expects a root or mounts to be specified.
+        let root = &Self::get_root(state)?;
+
+        // Downcast path to FsPath.
+        let abs_path = abs_path.downcast_ref::<FsPath>().unwrap().as_ref();
+        if !abs_path.starts_with(std::path::MAIN_SEPARATOR.to_string()) {
+            return errors::new_error_t(format!(
+                r#"expected resource path to be an absolute path starting with a path separator, {:?}"#,
+                abs_path
+            ));
+        }
+
+        // Clean path.
+        let clean_path = &Self::clean_path(&root, &abs_path)?;
+
+        // SEC: Convert path to UTF-8 string.
+        to_utf8_string(clean_path)
+    }
+}
+
+fn to_utf8_string(path: &Path) -> Result<String> {
+    path.as_os_str()
+        .to_owned()
+        .into_string()
+        .map_err(|e| errors::new_error(format!("converting path name to utf-8 string {:?}", e)))
 }
 
 impl FsRoot {
@@ -100,19 +194,15 @@ impl PermissionType for Fs {
     fn map(
         &self,
         allow_list: Vec<Box<dyn Resource>>,
-        state: &Option<Box<dyn State>>,
+        state: &StateMap,
     ) -> Result<Vec<Box<dyn Resource>>> {
+        // Without a root or mounts, paths are mapped once the set bounds one that has them. See `map_to_root`.
+        let root = Self::get_root(state).ok();
+
         // Canonicalize every dir in the allow list.
         let canon_list = allow_list
             .iter()
             .map(|dir| {
-                // Downcast state to Root. Expects a root to be specified.
-                let root = if let Some(state) = &state {
-                    state.downcast_ref::<FsRoot>().unwrap().as_ref()
-                } else {
-                    return errors::permission_error_t("root path not specified");
-                };
-
                 // Ensuring path starts with a separator.
                 let abs_path = dir.downcast_ref::<FsPath>().unwrap().as_ref();
                 if !abs_path.starts_with(std::path::MAIN_SEPARATOR.to_string()) {
@@ -134,37 +224,20 @@ impl PermissionType for Fs {
                     ));
                 }
 
-                // Clean path.
-                let clean_full_dir = Self::clean_path(root, abs_path)?;
+                match &root {
+                    Some(root) => Ok(Self::map_path(root, abs_path)?.into()),
+                    None => {
+                        // Invalid patterns are still rejected early.
+                        Glob::new(&to_utf8_string(abs_path)?)?;
 
-                debug!("Allowed path = {:?}", clean_full_dir);
+                        let fs_path = FsPath {
+                            path: abs_path.to_owned(),
+                            glob: None,
+                        };
 
-                let re_sep = utilities::path::get_platform_sep_pattern();
-
-                // SEC: Convert path to UTF-8 string.
-                let path_string = clean_full_dir
-                    .as_os_str()
-                    .to_owned()
-                    .into_string()
-                    .map_err(|e| {
-                        errors::new_error(format!("converting path name to utf-8 string {:?}", e))
-                    })?;
-
-                // SEC: Create regex that allows patterns like these:
-                // https://gist.github.com/appcypher/7074d219493fa2711c36b2d19fe75eb9#file-patterns-md
-                let pattern = path_string
-                    .replace("**", r".+")
-                    .replace("*", &format!(r"[^{}]+", re_sep));
-
-                // SEC: Ensuring the pattern matches against the whole string.
-                let re = Regex::new(&format!(r"^{}$", pattern)).unwrap();
-
-                let fs_path = FsPath {
-                    path: clean_full_dir,
-                    regex: Some(re),
-                };
-
-                Ok(fs_path.into())
+                        Ok(fs_path.into())
+                    }
+                }
             })
             .collect::<Result<Vec<Box<dyn Resource>>>>()?;
 
@@ -174,54 +247,138 @@ impl PermissionType for Fs {
     fn check(
         &self,
         abs_path: &Box<dyn Resource>,
-        allow_list: Rc<Vec<Box<dyn Resource>>>,
-        state: &Option<Box<dyn State>>,
+        allow_list: Arc<Vec<Box<dyn Resource>>>,
+        state: &StateMap,
     ) -> Result<()> {
-        // Downcast state to Root. Expects a root to be specified.
-        let root = if let Some(state) = state {
-            state.downcast_ref::<FsRoot>().unwrap().as_ref()
-        } else {
-            return errors::permission_error_t("root path not specified");
-        };
+        self.check_allowed(abs_path, allow_list, state).map(|_| ())
+    }
 
-        // Downcast path to FsPath.
-        let abs_path = abs_path.downcast_ref::<FsPath>().unwrap().as_ref();
-        if !abs_path.starts_with(std::path::MAIN_SEPARATOR.to_string()) {
-            return errors::new_error_t(format!(
-                r#"expected resource path to be an absolute path starting with a path separator, {:?}"#,
-                abs_path
-            ));
+    fn check_allowed(
+        &self,
+        abs_path: &Box<dyn Resource>,
+        allow_list: Arc<Vec<Box<dyn Resource>>>,
+        state: &StateMap,
+    ) -> Result<Option<Box<dyn Resource>>> {
+        // SEC: Paths must be in a mount, and writes to read-only mounts are rejected whatever the allow list says.
+        if let Some(mounts) = state.get::<MountFs>() {
+            let path = abs_path.downcast_ref::<FsPath>().unwrap().as_ref();
+            mounts.check(path, self.is_write())?;
         }
 
-        // Clean path.
-        let clean_path = &Self::clean_path(&root, &abs_path)?;
-
         // Check for any allowed dir that matches pattern.
-        for allowed_dir in allow_list.iter() {
-            // Downcast trait object to Path.
-            let fs_path = allowed_dir.downcast_ref::<FsPath>().unwrap();
-
-            // SEC: Convert path to UTF-8 string.
-            let path_string = clean_path
-                .as_os_str()
-                .to_owned()
-                .into_string()
-                .map_err(|e| {
-                    errors::new_error(format!("converting path name to utf-8 string {:?}", e))
-                })?;
-
-            // SEC: Check if path matches pattern.
-            if fs_path.regex.as_ref().unwrap().is_match(&path_string) {
-                return Ok(());
-            }
+        if let Some(allowed_dir) = self.find_match(abs_path, &allow_list, state)? {
+            return Ok(Some(allowed_dir));
         }
 
         errors::permission_error_t(format!(
             r#"permission type "{}" does not exist for file {:?}"#,
             self.get_type(),
-            abs_path
+            abs_path.downcast_ref::<FsPath>().unwrap().as_ref()
         ))
     }
+
+    fn find_match(
+        &self,
+        abs_path: &Box<dyn Resource>,
+        list: &[Box<dyn Resource>],
+        state: &StateMap,
+    ) -> Result<Option<Box<dyn Resource>>> {
+        let path_string = Self::get_resource_string(abs_path, state)?;
+
+        // Find the first dir that matches pattern.
+        for dir in list.iter() {
+            // Downcast trait object to Path.
+            let fs_path = dir.downcast_ref::<FsPath>().unwrap();
+
+            // SEC: Check if path matches pattern.
+            if fs_path.get_glob()?.is_match(&path_string) {
+                return Ok(Some(dir.clone()));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn find_match_under(
+        &self,
+        abs_path: &Box<dyn Resource>,
+        list: &[Box<dyn Resource>],
+        state: &StateMap,
+    ) -> Result<Option<Box<dyn Resource>>> {
+        // SEC: The path itself is moved or removed too, and it may be a file.
+        if let Some(entry) = self.find_match(abs_path, list, state)? {
+            return Ok(Some(entry));
+        }
+
+        // Paths under a dir start with the dir and a separator.
+        let mut dir_string = Self::get_resource_string(abs_path, state)?;
+        if !dir_string.ends_with(std::path::MAIN_SEPARATOR) {
+            dir_string.push(std::path::MAIN_SEPARATOR);
+        }
+
+        // Find the first dir whose pattern may match a path under the dir.
+        for dir in list.iter() {
+            let fs_path = dir.downcast_ref::<FsPath>().unwrap();
+
+            // SEC: Check if pattern may match a path under the dir.
+            if fs_path.get_glob()?.may_match_prefix(&dir_string) {
+                return Ok(Some(dir.clone()));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl ManifestPermissionType for Fs {
+    const SECTION: &'static str = "Fs";
+
+    fn variants() -> Vec<Self> {
+        vec![
+            Self::Open,
+            Self::Create,
+            Self::Read,
+            Self::Write,
+            Self::Execute,
+            Self::Info,
+        ]
+    }
+
+    fn parse_resource(resource: &str) -> Result<Box<dyn Resource>> {
+        Ok(FsPath::from(resource).into())
+    }
+
+    fn format_resource(resource: &Box<dyn Resource>, state: &StateMap) -> Result<String> {
+        let path = resource.downcast_ref::<FsPath>().unwrap().as_ref();
+
+        // Allowed paths are stored joined with the root, so the root is stripped to get the original path back.
+        let path = match state.get::<FsRoot>() {
+            Some(root) => Path::new(&std::path::MAIN_SEPARATOR.to_string())
+                .join(path.strip_prefix(root).unwrap_or(path)),
+            None => path.to_owned(),
+        };
+
+        path.into_os_string()
+            .into_string()
+            .map_err(|e| errors::new_error(format!("converting path name to utf-8 string {:?}", e)))
+    }
+
+    fn format_used_resource(resource: &Box<dyn Resource>, state: &StateMap) -> Result<String> {
+        // SEC: Allow list entries are glob patterns, so used paths are escaped to match only themselves.
+        Ok(glob::escape(&Self::format_resource(resource, state)?))
+    }
+}
+
+impl FsPath {
+    fn get_glob(&self) -> Result<&Glob> {
+        match &self.glob {
+            Some(glob) => Ok(glob),
+            None => errors::permission_error_t(format!(
+                r#"path {:?} is not mapped to a root dir"#,
+                self.path
+            )),
+        }
+    }
 }
 
 impl Resource for FsPath {
@@ -232,7 +389,7 @@ impl Resource for FsPath {
     fn get_debug(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("FsPath")
             .field("path", &self.path)
-            .field("regex", &self.regex)
+            .field("glob", &self.glob)
             .finish()
     }
 }
@@ -265,7 +422,7 @@ impl From<&Path> for FsPath {
     fn from(path: &Path) -> Self {
         Self {
             path: path.into(),
-            regex: None,
+            glob: None,
         }
     }
 }
@@ -274,7 +431,7 @@ impl From<&PathBuf> for FsPath {
     fn from(path: &PathBuf) -> Self {
         Self {
             path: path.into(),
-            regex: None,
+            glob: None,
         }
     }
 }
@@ -283,7 +440,7 @@ impl From<PathBuf> for FsPath {
     fn from(path: PathBuf) -> Self {
         Self {
             path: path,
-            regex: None,
+            glob: None,
         }
     }
 }
@@ -292,7 +449,7 @@ impl From<&str> for FsPath {
     fn from(path: &str) -> Self {
         Self {
             path: path.into(),
-            regex: None,
+            glob: None,
         }
     }
 }
@@ -301,7 +458,7 @@ impl From<&String> for FsPath {
     fn from(path: &String) -> Self {
         Self {
             path: path.into(),
-            regex: None,
+            glob: None,
         }
     }
 }
@@ -310,7 +467,7 @@ impl From<String> for FsPath {
     fn from(path: String) -> Self {
         Self {
             path: path.into(),
-            regex: None,
+            glob: None,
         }
     }
 }
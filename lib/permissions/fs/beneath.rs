@@ -0,0 +1,734 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use super::{Fs, FsRoot};
+use crate::vfs::{VfsDirEntry, VfsMetadata};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+use utilities::{errors, result::Result};
+
+/// How a file is opened with [`FsRoot::open`](struct@FsRoot).
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FsOpenOptions {
+    pub read: bool,
+    pub write: bool,
+    pub append: bool,
+    pub create: bool,
+    pub truncate: bool,
+}
+
+impl FsRoot {
+    /// Opens a path beneath the root.
+    ///
+    /// SEC: Symlinks are resolved one component at a time relative to the dirs already opened, so neither `..` nor a
+    /// symlink can take the path out of the root, even if the tree changes while it is being walked.
+    /// Symlinks with absolute targets are rejected. Escape attempts fail with a permission error.
+    ///
+    /// The other operations below walk the path the same way and then work relative to the dir holding the last
+    /// component. Host paths are never resolved again.
+    pub fn open(&self, path: &Path, options: &FsOpenOptions) -> Result<File> {
+        let relative_path = self.get_relative_path(path)?;
+        sys::open_beneath(self.as_ref(), &relative_path, path, options)
+    }
+
+    /// Gets the metadata of a path beneath the root. A symlink in the last component is only followed if
+    /// `follow_last` is set.
+    pub fn metadata(&self, path: &Path, follow_last: bool) -> Result<VfsMetadata> {
+        let relative_path = self.get_relative_path(path)?;
+        sys::metadata_beneath(self.as_ref(), &relative_path, path, follow_last)
+    }
+
+    pub fn read_dir(&self, path: &Path) -> Result<Vec<VfsDirEntry>> {
+        let relative_path = self.get_relative_path(path)?;
+        sys::read_dir_beneath(self.as_ref(), &relative_path, path)
+    }
+
+    pub fn create_dir(&self, path: &Path) -> Result<()> {
+        let relative_path = self.get_relative_path(path)?;
+        sys::create_dir_beneath(self.as_ref(), &relative_path, path)
+    }
+
+    /// Removes a file or an empty dir, or a dir and its content if `recursive` is set. Symlinks are not followed.
+    pub fn remove(&self, path: &Path, recursive: bool) -> Result<()> {
+        let relative_path = self.get_relative_path(path)?;
+        sys::remove_beneath(self.as_ref(), &relative_path, path, recursive)
+    }
+
+    /// Renames a file or dir. Symlinks in the last components are not followed.
+    pub fn rename(&self, from_path: &Path, to_path: &Path) -> Result<()> {
+        let from_relative_path = self.get_relative_path(from_path)?;
+        let to_relative_path = self.get_relative_path(to_path)?;
+
+        sys::rename_beneath(
+            self.as_ref(),
+            (&from_relative_path, from_path),
+            (&to_relative_path, to_path),
+        )
+    }
+
+    /// Gets the components of a path relative to the root.
+    fn get_relative_path(&self, path: &Path) -> Result<PathBuf> {
+        let clean_path = Fs::clean_path(self.as_ref(), path)?;
+
+        match clean_path.strip_prefix(self.as_ref()) {
+            Ok(relative_path) => Ok(relative_path.to_owned()),
+            Err(_) => escape_error(path),
+        }
+    }
+}
+
+fn escape_error<T>(path: &Path) -> Result<T> {
+    errors::permission_error_t(format!("path {:?} escapes the root dir", path))
+}
+
+#[cfg(unix)]
+mod sys {
+    use super::{escape_error, FsOpenOptions};
+    use crate::vfs::{VfsDirEntry, VfsFileType, VfsMetadata};
+    use std::{
+        collections::VecDeque,
+        convert::TryInto,
+        ffi::{CStr, CString, OsStr, OsString},
+        fs::File,
+        io,
+        os::unix::{
+            ffi::{OsStrExt, OsStringExt},
+            io::{AsRawFd, FromRawFd},
+        },
+        path::{Component, Path, PathBuf},
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+    use utilities::{
+        errors,
+        result::{Context, Result},
+    };
+
+    // Same as the limit Linux places on symlinks followed in a single lookup.
+    const MAX_SYMLINKS: usize = 40;
+
+    // Dirs in the middle of a path only need to be traversed, not read.
+    #[cfg(target_os = "linux")]
+    const DIR_FLAGS: libc::c_int = libc::O_PATH | libc::O_DIRECTORY;
+
+    #[cfg(not(target_os = "linux"))]
+    const DIR_FLAGS: libc::c_int = libc::O_RDONLY | libc::O_DIRECTORY;
+
+    pub(super) fn open_beneath(
+        root: &Path,
+        relative_path: &Path,
+        path: &Path,
+        options: &FsOpenOptions,
+    ) -> Result<File> {
+        let flags = get_flags(options);
+
+        walk(root, relative_path, path, |dir, name| {
+            openat(dir, name, flags, 0o666).map(Some)
+        })
+    }
+
+    pub(super) fn metadata_beneath(
+        root: &Path,
+        relative_path: &Path,
+        path: &Path,
+        follow_last: bool,
+    ) -> Result<VfsMetadata> {
+        let (dir, name) = open_parent(root, relative_path, path, follow_last)?;
+        let stat = fstatat(&dir, &name).context(format!("getting metadata of {:?}", path))?;
+
+        Ok(to_metadata(&stat))
+    }
+
+    pub(super) fn read_dir_beneath(
+        root: &Path,
+        relative_path: &Path,
+        path: &Path,
+    ) -> Result<Vec<VfsDirEntry>> {
+        let context = || format!("reading dir {:?}", path);
+        let (dir, name) = open_parent(root, relative_path, path, true)?;
+
+        let dir = openat(&dir, &name, libc::O_RDONLY | libc::O_DIRECTORY, 0).context(context())?;
+        let entries = read_dir_at(&dir).context(context())?;
+
+        Ok(entries
+            .into_iter()
+            .map(|(name, file_type)| VfsDirEntry {
+                name: name.to_string_lossy().into_owned(),
+                file_type,
+            })
+            .collect())
+    }
+
+    pub(super) fn create_dir_beneath(root: &Path, relative_path: &Path, path: &Path) -> Result<()> {
+        let (dir, name) = open_parent(root, relative_path, path, false)?;
+
+        // SAFETY: See `call_at`.
+        call_at(&dir, &name, |fd, name| unsafe {
+            libc::mkdirat(fd, name, 0o777)
+        })
+        .context(format!("creating dir {:?}", path))
+    }
+
+    pub(super) fn remove_beneath(
+        root: &Path,
+        relative_path: &Path,
+        path: &Path,
+        recursive: bool,
+    ) -> Result<()> {
+        let (dir, name) = open_parent(root, relative_path, path, false)?;
+
+        let result = if recursive {
+            remove_all_at(&dir, &name)
+        } else {
+            remove_at(&dir, &name)
+        };
+
+        result.context(format!("removing {:?}", path))
+    }
+
+    pub(super) fn rename_beneath(
+        root: &Path,
+        (from_relative_path, from_path): (&Path, &Path),
+        (to_relative_path, to_path): (&Path, &Path),
+    ) -> Result<()> {
+        let (from_dir, from_name) = open_parent(root, from_relative_path, from_path, false)?;
+        let (to_dir, to_name) = open_parent(root, to_relative_path, to_path, false)?;
+
+        renameat(&from_dir, &from_name, &to_dir, &to_name)
+            .context(format!("renaming {:?} to {:?}", from_path, to_path))
+    }
+
+    /// Walks to the dir holding the last component of the path and returns it with the name of the component.
+    ///
+    /// A symlink in the last component is only expanded if `follow_last` is set. The component does not have to exist.
+    fn open_parent(
+        root: &Path,
+        relative_path: &Path,
+        path: &Path,
+        follow_last: bool,
+    ) -> Result<(File, OsString)> {
+        walk(root, relative_path, path, |dir, name| {
+            if follow_last && readlinkat(dir, name).is_ok() {
+                return Ok(None);
+            }
+
+            Ok(Some((dir.try_clone()?, name.to_owned())))
+        })
+    }
+
+    /// Walks the path beneath the root one component at a time and passes the last one to `on_last`.
+    ///
+    /// `on_last` gets the dir holding the last component and the name of the component.
+    /// It returns `None`, or fails, to have a symlink in the last component expanded.
+    fn walk<T>(
+        root: &Path,
+        relative_path: &Path,
+        path: &Path,
+        mut on_last: impl FnMut(&File, &OsStr) -> io::Result<Option<T>>,
+    ) -> Result<T> {
+        let context = || format!("opening {:?} beneath the root dir", path);
+
+        // The stack of opened dirs. The root is always at the bottom.
+        let mut dirs = vec![File::open(root).context(context())?];
+
+        let mut pending = relative_path
+            .components()
+            .map(|component| component.as_os_str().to_owned())
+            .collect::<VecDeque<OsString>>();
+
+        // The root itself.
+        if pending.is_empty() {
+            pending.push_back(OsString::from("."));
+        }
+
+        let mut symlinks = 0;
+        while let Some(name) = pending.pop_front() {
+            let is_last = pending.is_empty();
+
+            if name == ".." {
+                // SEC: Popping the root would leave it.
+                if dirs.len() == 1 {
+                    return escape_error(path);
+                }
+
+                dirs.pop();
+
+                // Paths ending in ".." resolve to a dir.
+                if is_last {
+                    pending.push_back(OsString::from("."));
+                }
+
+                continue;
+            }
+
+            if name == "." && !is_last {
+                continue;
+            }
+
+            let dir = dirs.last().unwrap();
+            let error = if is_last {
+                match on_last(dir, &name) {
+                    Ok(Some(value)) => return Ok(value),
+                    Ok(None) => None,
+                    Err(error) => Some(error),
+                }
+            } else {
+                match openat(dir, &name, DIR_FLAGS, 0) {
+                    Ok(file) => {
+                        dirs.push(file);
+                        continue;
+                    }
+                    Err(error) => Some(error),
+                }
+            };
+
+            // SEC: Opening with `O_NOFOLLOW` fails on symlinks, which are then expanded in place of the component.
+            let target = match (readlinkat(dir, &name), error) {
+                (Ok(target), _) => target,
+                (Err(_), Some(error)) => return Err::<T, _>(error).context(context()),
+                (Err(error), None) => return Err::<T, _>(error).context(context()),
+            };
+
+            symlinks += 1;
+            if symlinks > MAX_SYMLINKS {
+                return errors::new_error_t(format!(
+                    "too many levels of symbolic links in {:?}",
+                    path
+                ));
+            }
+
+            // SEC: Absolute targets are resolved against the host root.
+            if target.has_root() {
+                return escape_error(path);
+            }
+
+            for component in target.components().rev() {
+                match component {
+                    Component::Normal(_) | Component::ParentDir | Component::CurDir => {
+                        pending.push_front(component.as_os_str().to_owned())
+                    }
+                    _ => return escape_error(path),
+                }
+            }
+        }
+
+        unreachable!("the last component always returns")
+    }
+
+    fn remove_at(dir: &File, name: &OsStr) -> io::Result<()> {
+        let flags = if is_dir(&fstatat(dir, name)?) {
+            libc::AT_REMOVEDIR
+        } else {
+            0
+        };
+
+        // SAFETY: See `call_at`.
+        call_at(dir, name, |fd, name| unsafe {
+            libc::unlinkat(fd, name, flags)
+        })
+    }
+
+    /// Removes an entry of a dir and, if it is a dir, everything under it.
+    fn remove_all_at(dir: &File, name: &OsStr) -> io::Result<()> {
+        if !is_dir(&fstatat(dir, name)?) {
+            return remove_at(dir, name);
+        }
+
+        // SEC: Opened with `O_NOFOLLOW`, so a dir swapped for a symlink in the meantime is not followed.
+        let child = openat(dir, name, libc::O_RDONLY | libc::O_DIRECTORY, 0)?;
+        for (child_name, _) in read_dir_at(&child)? {
+            remove_all_at(&child, &child_name)?;
+        }
+
+        remove_at(dir, name)
+    }
+
+    /// Lists the entries of an open dir, without `.` and `..`.
+    fn read_dir_at(dir: &File) -> io::Result<Vec<(OsString, VfsFileType)>> {
+        // The dir stream owns and closes its own descriptor.
+        // SAFETY: `dir` stays open for the duration of the call.
+        let fd = unsafe { libc::fcntl(dir.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: `fd` is a newly duplicated descriptor that nothing else owns.
+        let stream = unsafe { libc::fdopendir(fd) };
+        if stream.is_null() {
+            let error = io::Error::last_os_error();
+            // SAFETY: `fd` was not taken over by a dir stream.
+            unsafe { libc::close(fd) };
+            return Err(error);
+        }
+
+        let mut entries = vec![];
+        let result = loop {
+            // NOTE: Read errors also end the stream. Removing a dir that was not fully listed still fails after.
+            // SAFETY: `stream` is open, and the entry is only used until the next call.
+            let entry = unsafe { libc::readdir(stream) };
+            if entry.is_null() {
+                break Ok(());
+            }
+
+            // SAFETY: `entry` points to a valid entry with a nul-terminated name.
+            let (name, d_type) = unsafe {
+                let entry = &*entry;
+                (CStr::from_ptr(entry.d_name.as_ptr()), entry.d_type)
+            };
+
+            let name = OsStr::from_bytes(name.to_bytes());
+            if name == "." || name == ".." {
+                continue;
+            }
+
+            let file_type = match d_type {
+                libc::DT_DIR => VfsFileType::Dir,
+                libc::DT_LNK => VfsFileType::Symlink,
+                // Not every filesystem fills in the type.
+                libc::DT_UNKNOWN => match fstatat(dir, name) {
+                    Ok(stat) => to_file_type(stat.st_mode),
+                    Err(error) => break Err(error),
+                },
+                _ => VfsFileType::File,
+            };
+
+            entries.push((name.to_owned(), file_type));
+        };
+
+        // SAFETY: `stream` is open and not used after.
+        unsafe { libc::closedir(stream) };
+
+        result.map(|_| entries)
+    }
+
+    fn renameat(
+        from_dir: &File,
+        from_name: &OsStr,
+        to_dir: &File,
+        to_name: &OsStr,
+    ) -> io::Result<()> {
+        let from_name = CString::new(from_name.as_bytes())?;
+        let to_name = CString::new(to_name.as_bytes())?;
+
+        // SAFETY: The names are valid C strings and the dirs stay open for the duration of the call.
+        let code = unsafe {
+            libc::renameat(
+                from_dir.as_raw_fd(),
+                from_name.as_ptr(),
+                to_dir.as_raw_fd(),
+                to_name.as_ptr(),
+            )
+        };
+
+        to_result(code)
+    }
+
+    /// Gets the status of an entry of a dir. Symlinks are not followed.
+    fn fstatat(dir: &File, name: &OsStr) -> io::Result<libc::stat> {
+        let name = CString::new(name.as_bytes())?;
+
+        // SAFETY: `stat` is plain data, filled in by the call below.
+        let mut stat = unsafe { std::mem::zeroed::<libc::stat>() };
+
+        // SAFETY: `name` is a valid C string, `stat` is writable and `dir` stays open for the duration of the call.
+        let code = unsafe {
+            libc::fstatat(
+                dir.as_raw_fd(),
+                name.as_ptr(),
+                &mut stat,
+                libc::AT_SYMLINK_NOFOLLOW,
+            )
+        };
+
+        to_result(code)?;
+
+        Ok(stat)
+    }
+
+    /// Calls an `*at` function on an entry of a dir.
+    ///
+    /// SAFETY: The descriptor and name passed to `f` are valid for the duration of the call.
+    fn call_at(
+        dir: &File,
+        name: &OsStr,
+        f: impl FnOnce(libc::c_int, *const libc::c_char) -> libc::c_int,
+    ) -> io::Result<()> {
+        let name = CString::new(name.as_bytes())?;
+        to_result(f(dir.as_raw_fd(), name.as_ptr()))
+    }
+
+    fn to_result(code: libc::c_int) -> io::Result<()> {
+        if code < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    fn is_dir(stat: &libc::stat) -> bool {
+        to_file_type(stat.st_mode) == VfsFileType::Dir
+    }
+
+    fn to_file_type(mode: libc::mode_t) -> VfsFileType {
+        match mode & libc::S_IFMT {
+            libc::S_IFDIR => VfsFileType::Dir,
+            libc::S_IFLNK => VfsFileType::Symlink,
+            _ => VfsFileType::File,
+        }
+    }
+
+    fn to_metadata(stat: &libc::stat) -> VfsMetadata {
+        VfsMetadata {
+            file_type: to_file_type(stat.st_mode),
+            len: stat.st_size as u64,
+            readonly: stat.st_mode & 0o222 == 0,
+            modified: to_system_time(stat.st_mtime, stat.st_mtime_nsec),
+            accessed: to_system_time(stat.st_atime, stat.st_atime_nsec),
+            created: None, // Not part of `stat`.
+        }
+    }
+
+    // The field types differ across platforms.
+    fn to_system_time(secs: impl TryInto<u64>, nanos: impl TryInto<u32>) -> Option<SystemTime> {
+        let secs = secs.try_into().ok()?;
+        let nanos = nanos.try_into().ok()?;
+
+        UNIX_EPOCH.checked_add(Duration::new(secs, nanos))
+    }
+
+    fn get_flags(options: &FsOpenOptions) -> libc::c_int {
+        let mut flags = match (options.read, options.write || options.append) {
+            (true, true) => libc::O_RDWR,
+            (false, true) => libc::O_WRONLY,
+            _ => libc::O_RDONLY,
+        };
+
+        if options.append {
+            flags |= libc::O_APPEND;
+        }
+
+        if options.create {
+            flags |= libc::O_CREAT;
+        }
+
+        if options.truncate {
+            flags |= libc::O_TRUNC;
+        }
+
+        flags
+    }
+
+    fn openat(
+        dir: &File,
+        name: &OsStr,
+        flags: libc::c_int,
+        mode: libc::c_uint,
+    ) -> io::Result<File> {
+        let name = CString::new(name.as_bytes())?;
+
+        // SAFETY: `name` is a valid C string and `dir` stays open for the duration of the call.
+        let fd = unsafe {
+            libc::openat(
+                dir.as_raw_fd(),
+                name.as_ptr(),
+                flags | libc::O_NOFOLLOW | libc::O_CLOEXEC,
+                mode,
+            )
+        };
+
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: `fd` is a newly opened descriptor that nothing else owns.
+        Ok(unsafe { File::from_raw_fd(fd) })
+    }
+
+    fn readlinkat(dir: &File, name: &OsStr) -> io::Result<PathBuf> {
+        let name = CString::new(name.as_bytes())?;
+        let mut buf = vec![0u8; libc::PATH_MAX as usize];
+
+        // SAFETY: `name` is a valid C string and `buf` is writable for its whole length.
+        let len = unsafe {
+            libc::readlinkat(
+                dir.as_raw_fd(),
+                name.as_ptr(),
+                buf.as_mut_ptr() as *mut libc::c_char,
+                buf.len(),
+            )
+        };
+
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // A full buffer means the target may have been truncated.
+        if len as usize == buf.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "symbolic link target is too long",
+            ));
+        }
+
+        buf.truncate(len as usize);
+
+        Ok(PathBuf::from(OsString::from_vec(buf)))
+    }
+}
+
+// TODO(appcypher): SEC: The check below is racy. Use a handle-based walk on Windows.
+#[cfg(not(unix))]
+mod sys {
+    use super::{escape_error, FsOpenOptions};
+    use crate::vfs::{VfsDirEntry, VfsMetadata};
+    use std::{
+        fs::{self, File, OpenOptions},
+        path::{Path, PathBuf},
+    };
+    use utilities::result::{Context, Result};
+
+    pub(super) fn open_beneath(
+        root: &Path,
+        relative_path: &Path,
+        path: &Path,
+        options: &FsOpenOptions,
+    ) -> Result<File> {
+        let full_path = root.join(relative_path);
+
+        // SEC: Resolve existing paths, or the parent of a path about to be created, and ensure it stays under the root.
+        let resolved_path = match fs::canonicalize(&full_path) {
+            Ok(resolved_path) => resolved_path,
+            Err(_) if options.create => match full_path.parent() {
+                Some(parent) => fs::canonicalize(parent)
+                    .context(format!("opening {:?} beneath the root dir", path))?,
+                None => return escape_error(path),
+            },
+            Err(error) => {
+                return Err::<File, _>(error)
+                    .context(format!("opening {:?} beneath the root dir", path))
+            }
+        };
+
+        if !resolved_path.starts_with(root) {
+            return escape_error(path);
+        }
+
+        OpenOptions::new()
+            .read(options.read)
+            .write(options.write)
+            .append(options.append)
+            .truncate(options.truncate)
+            .create(options.create)
+            .open(&full_path)
+            .context(format!("opening {:?} beneath the root dir", path))
+    }
+
+    pub(super) fn metadata_beneath(
+        root: &Path,
+        relative_path: &Path,
+        path: &Path,
+        follow_last: bool,
+    ) -> Result<VfsMetadata> {
+        let host_path = resolve_beneath(root, relative_path, path, follow_last)?;
+
+        let metadata = if follow_last {
+            fs::metadata(&host_path)
+        } else {
+            fs::symlink_metadata(&host_path)
+        };
+
+        let metadata = metadata.context(format!("getting metadata of {:?}", path))?;
+
+        Ok(VfsMetadata::from(&metadata))
+    }
+
+    pub(super) fn read_dir_beneath(
+        root: &Path,
+        relative_path: &Path,
+        path: &Path,
+    ) -> Result<Vec<VfsDirEntry>> {
+        let context = || format!("reading dir {:?}", path);
+        let host_path = resolve_beneath(root, relative_path, path, true)?;
+
+        let mut entries = vec![];
+        for entry in fs::read_dir(&host_path).context(context())? {
+            let entry = entry.context(context())?;
+            let metadata = entry.metadata().context(context())?;
+
+            entries.push(VfsDirEntry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                file_type: VfsMetadata::from(&metadata).file_type,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    pub(super) fn create_dir_beneath(root: &Path, relative_path: &Path, path: &Path) -> Result<()> {
+        let host_path = resolve_beneath(root, relative_path, path, false)?;
+
+        fs::create_dir(&host_path).context(format!("creating dir {:?}", path))
+    }
+
+    pub(super) fn remove_beneath(
+        root: &Path,
+        relative_path: &Path,
+        path: &Path,
+        recursive: bool,
+    ) -> Result<()> {
+        let context = || format!("removing {:?}", path);
+        let host_path = resolve_beneath(root, relative_path, path, false)?;
+
+        let metadata = fs::symlink_metadata(&host_path).context(context())?;
+        if !metadata.is_dir() {
+            fs::remove_file(&host_path).context(context())
+        } else if recursive {
+            fs::remove_dir_all(&host_path).context(context())
+        } else {
+            fs::remove_dir(&host_path).context(context())
+        }
+    }
+
+    pub(super) fn rename_beneath(
+        root: &Path,
+        (from_relative_path, from_path): (&Path, &Path),
+        (to_relative_path, to_path): (&Path, &Path),
+    ) -> Result<()> {
+        let from_host_path = resolve_beneath(root, from_relative_path, from_path, false)?;
+        let to_host_path = resolve_beneath(root, to_relative_path, to_path, false)?;
+
+        fs::rename(&from_host_path, &to_host_path)
+            .context(format!("renaming {:?} to {:?}", from_path, to_path))
+    }
+
+    fn resolve_beneath(
+        root: &Path,
+        relative_path: &Path,
+        path: &Path,
+        follow_last: bool,
+    ) -> Result<PathBuf> {
+        // The root itself.
+        if relative_path.as_os_str().is_empty() {
+            return Ok(root.to_owned());
+        }
+
+        let full_path = root.join(relative_path);
+
+        // SEC: Resolve the parent, and the path itself if followed, and ensure it stays under the root.
+        let resolved_path = match fs::canonicalize(&full_path) {
+            Ok(resolved_path) if follow_last => resolved_path,
+            _ => match (full_path.parent(), full_path.file_name()) {
+                (Some(parent), Some(name)) => fs::canonicalize(parent)
+                    .context(format!("resolving {:?} beneath the root dir", path))?
+                    .join(name),
+                _ => return escape_error(path),
+            },
+        };
+
+        if !resolved_path.starts_with(root) {
+            return escape_error(path);
+        }
+
+        Ok(resolved_path)
+    }
+}
@@ -0,0 +1,343 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+//! Glob patterns for matching paths.
+//!
+//! | Pattern  | Matches                                                              |
+//! |----------|----------------------------------------------------------------------|
+//! | `?`      | Any single character except the separator.                           |
+//! | `*`      | Zero or more characters except the separator.                        |
+//! | `**`     | As a whole segment, zero or more segments. Otherwise the same as `*`. |
+//! | `[a-z]`  | Any character in the class except the separator. `[!a-z]` negates it. |
+//! | `{a,b}`  | Any of the comma-separated patterns.                                 |
+//! | `\x`     | The character `x` literally (not supported on Windows).              |
+//!
+//! Every other character matches itself. A pattern always matches the whole path.
+
+use regex::Regex;
+use std::path::MAIN_SEPARATOR;
+use utilities::{
+    errors,
+    result::{Context, Result},
+};
+
+// The separator doubles as the escape character on Windows, so escaping is only supported elsewhere.
+const ESCAPE: Option<char> = if MAIN_SEPARATOR == '\\' {
+    None
+} else {
+    Some('\\')
+};
+
+/// A compiled glob pattern.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pattern: String,
+    regex: Regex,
+    literal_prefix: String, // The part of the pattern before any glob syntax.
+    is_literal: bool,       // Set if the whole pattern is literal.
+}
+
+struct Parser<'a> {
+    pattern: &'a str,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Self> {
+        Self::with_literal_prefix("", pattern)
+    }
+
+    /// Creates a glob that matches paths starting with `prefix`, which is matched literally, followed by `pattern`.
+    ///
+    /// SEC: Useful when the prefix is a root dir that may contain glob syntax.
+    pub fn with_literal_prefix(prefix: &str, pattern: &str) -> Result<Self> {
+        let body = Parser::new(pattern).parse()?;
+        let (literal_prefix, is_literal) = get_literal_prefix(pattern);
+
+        // SEC: `(?s)` makes `.` match newlines, which are valid in file names. Anchors ensure whole paths are matched.
+        let regex_string = format!("(?s)^{}{}$", regex::escape(prefix), body);
+        let regex = Regex::new(&regex_string)
+            .context(format!(r#"compiling glob pattern "{}""#, pattern))?;
+
+        Ok(Self {
+            pattern: format!("{}{}", prefix, pattern),
+            regex,
+            literal_prefix: format!("{}{}", prefix, literal_prefix),
+            is_literal,
+        })
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        self.regex.is_match(path)
+    }
+
+    /// Checks if the pattern may match a path starting with `prefix`, e.g. a path under a dir.
+    ///
+    /// SEC: Only the literal part of the pattern is compared, so this can be true for patterns that never match such a
+    /// path. It is never false for patterns that do.
+    pub fn may_match_prefix(&self, prefix: &str) -> bool {
+        if self.literal_prefix.starts_with(prefix) {
+            return true;
+        }
+
+        !self.is_literal && prefix.starts_with(&self.literal_prefix)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+}
+
+/// Escapes a path so that it matches only itself when used as a pattern.
+///
+/// Without an escape character, e.g. on Windows, glob syntax is wrapped in a character class instead.
+pub fn escape(path: &str) -> String {
+    let mut pattern = String::with_capacity(path.len());
+
+    for c in path.chars() {
+        match c {
+            '?' | '*' | '[' | '{' | '}' => match ESCAPE {
+                Some(escape) => {
+                    pattern.push(escape);
+                    pattern.push(c);
+                }
+                None => pattern.push_str(&format!("[{}]", c)),
+            },
+            c if Some(c) == ESCAPE => {
+                pattern.push(c);
+                pattern.push(c);
+            }
+            c => pattern.push(c),
+        }
+    }
+
+    pattern
+}
+
+impl<'a> Parser<'a> {
+    fn new(pattern: &'a str) -> Self {
+        Self {
+            pattern,
+            chars: pattern.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn parse(mut self) -> Result<String> {
+        let regex = self.parse_sequence(false)?;
+
+        // A closing brace or comma outside of braces.
+        if let Some(c) = self.peek(0) {
+            return self.error(format!("unexpected {:?}", c));
+        }
+
+        Ok(regex)
+    }
+
+    fn parse_sequence(&mut self, in_braces: bool) -> Result<String> {
+        let sep = get_sep();
+        let mut regex = String::new();
+
+        while let Some(c) = self.peek(0) {
+            match c {
+                ',' | '}' if in_braces => break,
+                '}' => return self.error("unexpected '}'"),
+                '?' => {
+                    self.pos += 1;
+                    regex.push_str(&format!("[^{}]", sep));
+                }
+                '*' => regex.push_str(&self.parse_stars(in_braces)),
+                '[' => regex.push_str(&self.parse_class()?),
+                '{' => regex.push_str(&self.parse_alternation()?),
+                c if c == MAIN_SEPARATOR => {
+                    // A trailing `/**` matches the dir itself and everything under it.
+                    if self.peek(1) == Some('*')
+                        && self.peek(2) == Some('*')
+                        && self.is_boundary(3, in_braces)
+                    {
+                        self.pos += 3;
+                        regex.push_str(&format!("(?:{}.*)?", sep));
+                    } else {
+                        self.pos += 1;
+                        regex.push_str(&sep);
+                    }
+                }
+                c if Some(c) == ESCAPE => {
+                    self.pos += 1;
+                    match self.next() {
+                        Some(c) => regex.push_str(&escape_char(c)),
+                        None => return self.error("trailing escape character"),
+                    }
+                }
+                c => {
+                    self.pos += 1;
+                    regex.push_str(&escape_char(c));
+                }
+            }
+        }
+
+        Ok(regex)
+    }
+
+    fn parse_stars(&mut self, in_braces: bool) -> String {
+        let sep = get_sep();
+
+        // Count consecutive stars.
+        let start = self.pos;
+        while self.peek(0) == Some('*') {
+            self.pos += 1;
+        }
+
+        let count = self.pos - start;
+        let at_segment_start = start == 0
+            || (in_braces && matches!(self.chars[start - 1], '{' | ','))
+            || self.chars[start - 1] == MAIN_SEPARATOR;
+
+        if count == 2 && at_segment_start {
+            // `**/` matches zero or more segments.
+            if self.peek(0) == Some(MAIN_SEPARATOR) {
+                self.pos += 1;
+                return format!("(?:[^{sep}]+{sep})*", sep = sep);
+            }
+
+            // `**` on its own matches everything.
+            if self.is_boundary(0, in_braces) {
+                return String::from(".*");
+            }
+        }
+
+        format!("[^{}]*", sep)
+    }
+
+    fn parse_class(&mut self) -> Result<String> {
+        let sep = get_sep();
+        self.pos += 1; // Skip '['.
+
+        let negated = matches!(self.peek(0), Some('!') | Some('^'));
+        if negated {
+            self.pos += 1;
+        }
+
+        let mut items = String::new();
+        let mut first = true;
+        loop {
+            let c = match self.next_class_char()? {
+                Some((']', false)) if !first => break,
+                Some((c, _)) => c,
+                None => return self.error("unclosed character class"),
+            };
+
+            first = false;
+
+            // Range like `a-z`. A `-` before the closing bracket is literal.
+            if self.peek(0) == Some('-') && !matches!(self.peek(1), Some(']') | None) {
+                self.pos += 1;
+
+                let end = match self.next_class_char()? {
+                    Some((end, _)) => end,
+                    None => return self.error("unclosed character class"),
+                };
+
+                if end < c {
+                    return self.error(format!("invalid range {:?}-{:?}", c, end));
+                }
+
+                items.push_str(&format!("{}-{}", escape_char(c), escape_char(end)));
+            } else {
+                items.push_str(&escape_char(c));
+            }
+        }
+
+        // SEC: Character classes never match the separator.
+        if negated {
+            Ok(format!("[^{}{}]", items, sep))
+        } else {
+            Ok(format!("[{}&&[^{}]]", items, sep))
+        }
+    }
+
+    /// Gets the next character in a class and whether it is escaped.
+    fn next_class_char(&mut self) -> Result<Option<(char, bool)>> {
+        match self.next() {
+            Some(c) if Some(c) == ESCAPE => match self.next() {
+                Some(c) => Ok(Some((c, true))),
+                None => self.error("trailing escape character"),
+            },
+            Some(c) => Ok(Some((c, false))),
+            None => Ok(None),
+        }
+    }
+
+    fn parse_alternation(&mut self) -> Result<String> {
+        self.pos += 1; // Skip '{'.
+
+        let mut alternatives = vec![];
+        loop {
+            alternatives.push(self.parse_sequence(true)?);
+
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                _ => return self.error("unclosed '{'"),
+            }
+        }
+
+        Ok(format!("(?:{})", alternatives.join("|")))
+    }
+
+    /// Checks if the position at the offset ends a pattern or an alternative.
+    fn is_boundary(&self, offset: usize, in_braces: bool) -> bool {
+        match self.peek(offset) {
+            None => true,
+            Some(',') | Some('}') => in_braces,
+            _ => false,
+        }
+    }
+
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek(0);
+        if c.is_some() {
+            self.pos += 1;
+        }
+
+        c
+    }
+
+    fn error<T>(&self, message: impl AsRef<str>) -> Result<T> {
+        errors::new_error_t(format!(
+            r#"invalid glob pattern "{}", {}"#,
+            self.pattern,
+            message.as_ref()
+        ))
+    }
+}
+
+/// Gets the part of a pattern before any glob syntax, unescaped, and whether that is the whole pattern.
+fn get_literal_prefix(pattern: &str) -> (String, bool) {
+    let mut literal_prefix = String::new();
+
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '?' | '*' | '[' | '{' => return (literal_prefix, false),
+            c if Some(c) == ESCAPE => match chars.next() {
+                Some(c) => literal_prefix.push(c),
+                None => return (literal_prefix, false),
+            },
+            c => literal_prefix.push(c),
+        }
+    }
+
+    (literal_prefix, true)
+}
+
+fn get_sep() -> String {
+    regex::escape(&MAIN_SEPARATOR.to_string())
+}
+
+fn escape_char(c: char) -> String {
+    regex::escape(&c.to_string())
+}
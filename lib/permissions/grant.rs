@@ -0,0 +1,102 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use utilities::{errors, result::Result};
+
+/// Conditions on the grant of a permission type. A grant that has expired or been revoked is treated as missing.
+///
+/// ```ignore
+/// let revocation = Revocation::new();
+///
+/// let permissions = Permissions::builder()
+///     .add_permissions(&[HttpEvent::ResponseSend])?
+///     .add_grant(
+///         &[HttpEvent::ResponseSend],
+///         Grant::new()
+///             .expires_after(Duration::from_secs(60))
+///             .revocable(&revocation),
+///     )
+///     .build();
+///
+/// // Later, from the host.
+/// revocation.revoke();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Grant {
+    expires_at: Option<Instant>,
+    revocations: Vec<Revocation>,
+}
+
+/// Revokes the grants it is attached to, e.g. when the plan of a tenant is downgraded mid-request.
+///
+/// Clones revoke the same grants, so the host can keep one while the permissions are in use.
+#[derive(Debug, Clone, Default)]
+pub struct Revocation(Arc<AtomicBool>);
+
+impl Grant {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The grant expires at `deadline`. The earliest deadline wins.
+    pub fn expires_at(mut self, deadline: Instant) -> Self {
+        self.expires_at = Some(match self.expires_at {
+            Some(expires_at) => expires_at.min(deadline),
+            None => deadline,
+        });
+        self
+    }
+
+    pub fn expires_after(self, duration: Duration) -> Self {
+        self.expires_at(Instant::now() + duration)
+    }
+
+    pub fn revocable(mut self, revocation: &Revocation) -> Self {
+        self.revocations.push(revocation.clone());
+        self
+    }
+
+    /// Checks that the grant has neither expired nor been revoked.
+    pub fn check(&self) -> Result<()> {
+        if let Some(expires_at) = self.expires_at {
+            if Instant::now() >= expires_at {
+                return errors::permission_error_t("grant has expired");
+            }
+        }
+
+        if self.revocations.iter().any(Revocation::is_revoked) {
+            return errors::permission_error_t("grant has been revoked");
+        }
+
+        Ok(())
+    }
+
+    /// Adds the conditions of `other`. SEC: The conditions of both grants apply.
+    pub(super) fn merge(&mut self, other: &Grant) {
+        if let Some(deadline) = other.expires_at {
+            *self = std::mem::take(self).expires_at(deadline);
+        }
+
+        self.revocations.extend(other.revocations.iter().cloned());
+    }
+}
+
+impl Revocation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn revoke(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_revoked(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,70 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use super::{
+    fs::FsRoot, PermissionRegistry, PermissionTypeKey, PermissionsManifest, Resource, StateMap,
+};
+use std::{collections::BTreeMap, path::PathBuf, sync::Mutex};
+use utilities::result::Result;
+
+/// Records every permission a module uses so that a minimal manifest can be generated from them.
+///
+/// SEC: Permissions with a learner attached do not deny anything. Only use them in a trusted environment.
+#[derive(Debug, Default)]
+pub struct PermissionLearner {
+    used: Mutex<BTreeMap<PermissionTypeKey, Vec<Box<dyn Resource>>>>,
+    fs_root: Mutex<Option<PathBuf>>,
+}
+
+impl PermissionLearner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(super) fn record(
+        &self,
+        key: PermissionTypeKey,
+        resource: Option<&Box<dyn Resource>>,
+        state: &StateMap,
+    ) {
+        let mut used = self.used.lock().unwrap();
+        let resources = used.entry(key).or_default();
+        if let Some(resource) = resource {
+            resources.push(resource.clone());
+        }
+
+        // Keep the root so that the generated manifest resolves paths the same way.
+        if let Some(fs_root) = state.get::<FsRoot>() {
+            self.fs_root
+                .lock()
+                .unwrap()
+                .get_or_insert_with(|| fs_root.as_ref().to_owned());
+        }
+    }
+
+    /// Generates the minimal manifest that permits everything used so far.
+    pub fn to_manifest(&self, registry: &PermissionRegistry) -> Result<PermissionsManifest> {
+        let used = self.used.lock().unwrap();
+
+        let mut manifest = registry.create_manifest(
+            self.fs_root.lock().unwrap().clone(),
+            used.iter()
+                .map(|(key, resources)| (key, resources.as_slice())),
+            &StateMap::default(),
+            true,
+        )?;
+
+        // Resources used more than once are only allowed once.
+        for variants in manifest.permissions.values_mut() {
+            for allow_list in variants.values_mut() {
+                allow_list.sort();
+                allow_list.dedup();
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    pub fn clear(&self) {
+        self.used.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,343 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use super::{
+    env::Env,
+    events::event_http::HttpEvent,
+    fs::{Fs, FsRoot},
+    PermissionType, PermissionTypeKey, Permissions, Resource, StateMap,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap, convert::TryFrom, fmt::Debug, marker::PhantomData, path::PathBuf,
+};
+use utilities::{errors, result::Result};
+
+/// A declarative description of [`Permissions`](struct@Permissions) that can be loaded from any serde format like JSON, TOML or YAML.
+///
+/// Permissions are grouped in sections named after their permission type. Each variant maps to its allow list.
+///
+/// ```toml
+/// fs_root = "/srv/tenant"
+///
+/// [permissions.Fs]
+/// Read = ["/data/**"]
+///
+/// [permissions.HttpEvent]
+/// RequestRead = []
+///
+/// [deny.Fs]
+/// Read = ["/data/secrets/**"]
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PermissionsManifest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fs_root: Option<PathBuf>,
+    #[serde(default)]
+    pub permissions: BTreeMap<String, BTreeMap<String, Vec<String>>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub deny: BTreeMap<String, BTreeMap<String, Vec<String>>>,
+}
+
+/// A permission type that can be declared in a [`manifest`](struct@PermissionsManifest).
+pub trait ManifestPermissionType: PermissionType + Into<Box<dyn PermissionType>> + 'static {
+    /// The name of the manifest section, e.g. `Fs`.
+    const SECTION: &'static str;
+
+    /// Every variant of the permission type. Variants are named in the manifest by their `Debug` representation.
+    fn variants() -> Vec<Self>;
+
+    fn parse_resource(_resource: &str) -> Result<Box<dyn Resource>> {
+        errors::type_error_t(format!(
+            r#"permission type "{}" does not take an allow list"#,
+            Self::SECTION
+        ))
+    }
+
+    fn format_resource(_resource: &Box<dyn Resource>, _state: &StateMap) -> Result<String> {
+        errors::type_error_t(format!(
+            r#"permission type "{}" does not take an allow list"#,
+            Self::SECTION
+        ))
+    }
+
+    /// Formats a resource that was used, e.g. by a module in learning mode, as an allow list entry that matches only it.
+    fn format_used_resource(resource: &Box<dyn Resource>, state: &StateMap) -> Result<String> {
+        Self::format_resource(resource, state)
+    }
+}
+
+/// The permission types known to manifests.
+///
+/// The built-in permission types are registered by default. Custom ones are added with `register`.
+pub struct PermissionRegistry {
+    sections: BTreeMap<&'static str, Box<dyn ManifestSection>>,
+}
+
+/// Type-erased [`ManifestPermissionType`](trait@ManifestPermissionType).
+trait ManifestSection {
+    fn parse(
+        &self,
+        variant: &str,
+        allow_list: &[String],
+    ) -> Result<(Box<dyn PermissionType>, Vec<Box<dyn Resource>>)>;
+
+    fn get_variant(&self, key: &PermissionTypeKey) -> Option<String>;
+
+    fn format_allow_list(
+        &self,
+        allow_list: &[Box<dyn Resource>],
+        state: &StateMap,
+    ) -> Result<Vec<String>>;
+
+    fn format_used_list(&self, used: &[Box<dyn Resource>], state: &StateMap)
+        -> Result<Vec<String>>;
+}
+
+struct Section<T>(PhantomData<T>);
+
+impl PermissionRegistry {
+    /// Creates a registry with no permission type.
+    pub fn empty() -> Self {
+        Self {
+            sections: BTreeMap::new(),
+        }
+    }
+
+    pub fn register<T: ManifestPermissionType>(mut self) -> Self {
+        self.sections
+            .insert(T::SECTION, Box::new(Section::<T>(PhantomData)));
+        self
+    }
+}
+
+impl Permissions {
+    pub fn from_manifest(
+        manifest: &PermissionsManifest,
+        registry: &PermissionRegistry,
+    ) -> Result<Self> {
+        let mut builder = Self::builder();
+
+        // State must be added first because allow lists are mapped against it.
+        if let Some(fs_root) = &manifest.fs_root {
+            builder = builder.add_state(FsRoot::try_from(fs_root)?);
+        }
+
+        let permissions = registry.parse_sections(&manifest.permissions)?;
+        let deny_permissions = registry.parse_sections(&manifest.deny)?;
+
+        Ok(builder
+            .add_owned_permissions_with_allow_lists(permissions)?
+            .add_owned_permissions_with_deny_lists(deny_permissions)?
+            .build())
+    }
+
+    pub fn to_manifest(&self, registry: &PermissionRegistry) -> Result<PermissionsManifest> {
+        // SEC: Dropping the bounds would widen the permissions.
+        if !self.bounds.is_empty() {
+            return errors::permission_error_t("writing narrowed permissions to a manifest");
+        }
+
+        // SEC: Dropping the conditions on grants would make them last forever.
+        if !self.grants.is_empty() {
+            return errors::permission_error_t("writing conditional grants to a manifest");
+        }
+
+        let fs_root = self
+            .state
+            .get::<FsRoot>()
+            .map(|fs_root| fs_root.as_ref().to_owned());
+
+        let mut manifest = registry.create_manifest(
+            fs_root,
+            self.map
+                .iter()
+                .map(|(key, allow_list)| (key, allow_list.as_slice())),
+            &self.state,
+            false,
+        )?;
+
+        manifest.deny = registry
+            .create_manifest(
+                None,
+                self.deny_map
+                    .iter()
+                    .map(|(key, deny_list)| (key, deny_list.as_slice())),
+                &self.state,
+                false,
+            )?
+            .permissions;
+
+        Ok(manifest)
+    }
+}
+
+impl PermissionRegistry {
+    /// Parses a permission type named like `Fs.Read` and the resource it is checked for, if any.
+    pub fn parse(
+        &self,
+        name: &str,
+        resource: Option<&str>,
+    ) -> Result<(Box<dyn PermissionType>, Option<Box<dyn Resource>>)> {
+        let (section_name, variant) = match name.split_once('.') {
+            Some(parts) => parts,
+            None => {
+                return errors::type_error_t(format!(
+                    r#"expected permission type to be named like "Section.Variant", got "{}""#,
+                    name
+                ))
+            }
+        };
+
+        let section = self.get_section(section_name)?;
+        let resources = resource.into_iter().map(String::from).collect::<Vec<_>>();
+        let (permission_type, mut resources) = section.parse(variant, &resources)?;
+
+        Ok((permission_type, resources.pop()))
+    }
+
+    fn get_section(&self, section_name: &str) -> Result<&dyn ManifestSection> {
+        match self.sections.get(section_name) {
+            Some(section) => Ok(section.as_ref()),
+            None => errors::type_error_t(format!(
+                r#"unknown permission type "{}", expected one of {:?}"#,
+                section_name,
+                self.sections.keys().collect::<Vec<_>>()
+            )),
+        }
+    }
+
+    fn parse_sections(
+        &self,
+        sections: &BTreeMap<String, BTreeMap<String, Vec<String>>>,
+    ) -> Result<Vec<(Box<dyn PermissionType>, Vec<Box<dyn Resource>>)>> {
+        let mut permissions = vec![];
+        for (section_name, variants) in sections.iter() {
+            let section = self.get_section(section_name)?;
+
+            for (variant, resources) in variants.iter() {
+                permissions.push(section.parse(variant, resources)?);
+            }
+        }
+
+        Ok(permissions)
+    }
+
+    /// Creates a manifest from allow lists, or from the resources used if `used` is set.
+    pub(super) fn create_manifest<'a>(
+        &self,
+        fs_root: Option<PathBuf>,
+        permissions: impl Iterator<Item = (&'a PermissionTypeKey, &'a [Box<dyn Resource>])>,
+        state: &StateMap,
+        used: bool,
+    ) -> Result<PermissionsManifest> {
+        let mut manifest = PermissionsManifest {
+            fs_root,
+            ..Default::default()
+        };
+
+        for (key, allow_list) in permissions {
+            let (section_name, section, variant) = match self
+                .sections
+                .iter()
+                .find_map(|(name, section)| Some((name, section, section.get_variant(key)?)))
+            {
+                Some(found) => found,
+                None => {
+                    return errors::type_error_t(format!(
+                        "permission type {:?} is not registered",
+                        key
+                    ))
+                }
+            };
+
+            // SEC: Used resources are literal, so glob syntax in them must not grant more than was used.
+            let allow_list = if used {
+                section.format_used_list(allow_list, state)?
+            } else {
+                section.format_allow_list(allow_list, state)?
+            };
+
+            manifest
+                .permissions
+                .entry(section_name.to_string())
+                .or_default()
+                .insert(variant, allow_list);
+        }
+
+        Ok(manifest)
+    }
+}
+
+impl<T: ManifestPermissionType> ManifestSection for Section<T> {
+    fn parse(
+        &self,
+        variant: &str,
+        allow_list: &[String],
+    ) -> Result<(Box<dyn PermissionType>, Vec<Box<dyn Resource>>)> {
+        let permission_type = match T::variants()
+            .into_iter()
+            .find(|v| format!("{:?}", v) == variant)
+        {
+            Some(permission_type) => permission_type,
+            None => {
+                return errors::type_error_t(format!(
+                    r#"unknown permission type "{}::{}""#,
+                    T::SECTION,
+                    variant
+                ))
+            }
+        };
+
+        let allow_list = allow_list
+            .iter()
+            .map(|resource| T::parse_resource(resource))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((permission_type.into(), allow_list))
+    }
+
+    fn get_variant(&self, key: &PermissionTypeKey) -> Option<String> {
+        T::variants()
+            .into_iter()
+            .find(|v| &v.get_key() == key)
+            .map(|v| format!("{:?}", v))
+    }
+
+    fn format_allow_list(
+        &self,
+        allow_list: &[Box<dyn Resource>],
+        state: &StateMap,
+    ) -> Result<Vec<String>> {
+        allow_list
+            .iter()
+            .map(|resource| T::format_resource(resource, state))
+            .collect()
+    }
+
+    fn format_used_list(
+        &self,
+        used: &[Box<dyn Resource>],
+        state: &StateMap,
+    ) -> Result<Vec<String>> {
+        used.iter()
+            .map(|resource| T::format_used_resource(resource, state))
+            .collect()
+    }
+}
+
+impl Default for PermissionRegistry {
+    fn default() -> Self {
+        Self::empty()
+            .register::<Fs>()
+            .register::<HttpEvent>()
+            .register::<Env>()
+    }
+}
+
+impl Debug for PermissionRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PermissionRegistry")
+            .field(&self.sections.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
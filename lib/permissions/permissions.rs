@@ -1,31 +1,37 @@
 // Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
 
+use super::{Grant, PermissionCheck, PermissionLearner, PermissionObserver};
 use downcast_rs::{impl_downcast, Downcast};
+use log::warn;
 use std::any::{type_name, TypeId};
 use std::cmp::Eq;
 use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::iter::FromIterator;
-use std::rc::Rc;
+use std::sync::Arc;
 use utilities::{errors, result::Result};
 
-type PermissionMap = BTreeMap<PermissionTypeKey, Rc<Vec<Box<dyn Resource>>>>;
+pub(super) type PermissionMap = BTreeMap<PermissionTypeKey, Arc<Vec<Box<dyn Resource>>>>;
 
-pub trait Resource: Downcast {
+pub trait Resource: Downcast + Send + Sync {
     fn get_debug(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result;
     fn get_clone(&self) -> Box<dyn Resource>;
 }
 
-pub trait State: Downcast {
+pub trait State: Downcast + Send + Sync {
     fn get_debug(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result;
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
 pub struct PermissionTypeKey {
     pub type_id: TypeId,
     pub variant: i32,
 }
 
+/// The states of permission types, keyed by their type. A permission type looks up its own state, e.g. `Fs` gets `FsRoot`.
+#[derive(Default, Clone)]
+pub struct StateMap(BTreeMap<TypeId, Arc<dyn State>>);
+
 pub trait PermissionType: std::fmt::Debug {
     fn get_key<'a>(&self) -> PermissionTypeKey;
 
@@ -36,7 +42,7 @@ pub trait PermissionType: std::fmt::Debug {
     fn map(
         &self,
         allow_list: Vec<Box<dyn Resource>>,
-        _state: &Option<Box<dyn State>>,
+        _state: &StateMap,
     ) -> Result<Vec<Box<dyn Resource>>> {
         Ok(allow_list)
     }
@@ -44,22 +50,76 @@ pub trait PermissionType: std::fmt::Debug {
     fn check(
         &self,
         _resource: &Box<dyn Resource>,
-        _allow_list: Rc<Vec<Box<dyn Resource>>>,
-        _state: &Option<Box<dyn State>>,
+        _allow_list: Arc<Vec<Box<dyn Resource>>>,
+        _state: &StateMap,
     ) -> Result<()> {
         unimplemented!()
     }
+
+    /// Same as `check` but also gets the allow list entry that grants access to the resource, if known.
+    fn check_allowed(
+        &self,
+        resource: &Box<dyn Resource>,
+        allow_list: Arc<Vec<Box<dyn Resource>>>,
+        state: &StateMap,
+    ) -> Result<Option<Box<dyn Resource>>> {
+        self.check(resource, allow_list, state).map(|_| None)
+    }
+
+    /// Finds the entry of an allow or deny list that matches the resource.
+    ///
+    /// Deny entries are only supported by permission types that implement this.
+    fn find_match(
+        &self,
+        _resource: &Box<dyn Resource>,
+        _list: &[Box<dyn Resource>],
+        _state: &StateMap,
+    ) -> Result<Option<Box<dyn Resource>>> {
+        errors::permission_error_t(format!(
+            r#"permission type "{}" does not support deny entries"#,
+            self.get_type()
+        ))
+    }
+
+    /// Same as `find_match` but finds an entry that may match the resource or a resource nested under it, e.g. a file in
+    /// a dir.
+    ///
+    /// Nested resources are only supported by permission types that implement this.
+    fn find_match_under(
+        &self,
+        _resource: &Box<dyn Resource>,
+        _list: &[Box<dyn Resource>],
+        _state: &StateMap,
+    ) -> Result<Option<Box<dyn Resource>>> {
+        errors::permission_error_t(format!(
+            r#"permission type "{}" does not support nested resources"#,
+            self.get_type()
+        ))
+    }
 }
 
-#[derive(Default, Debug)]
+/// A compiled permission set.
+///
+/// Permissions are immutable once built and cheap to clone, so a set can be built once per tenant and shared by
+/// runtimes on different threads.
+#[derive(Default, Debug, Clone)]
 pub struct Permissions {
     pub map: PermissionMap,
-    pub state: Option<Box<dyn State>>,
+    pub deny_map: PermissionMap, // SEC: Deny entries always beat allow entries.
+    pub state: StateMap,
+    pub observers: Vec<Arc<dyn PermissionObserver>>,
+    pub learner: Option<Arc<PermissionLearner>>, // Set in learning mode.
+    pub grants: BTreeMap<PermissionTypeKey, Grant>, // Conditions on grants. Grants without one last forever.
+    pub bounds: Vec<Arc<Permissions>>, // SEC: Set by intersections. Checks must also pass every bound.
 }
 
 pub struct PermissionsBuilder {
     pub(super) map: PermissionMap,
-    pub(super) state: Option<Box<dyn State>>,
+    pub(super) deny_map: PermissionMap,
+    pub(super) state: StateMap,
+    pub(super) observers: Vec<Arc<dyn PermissionObserver>>,
+    pub(super) learner: Option<Arc<PermissionLearner>>,
+    pub(super) grants: BTreeMap<PermissionTypeKey, Grant>,
 }
 
 impl Permissions {
@@ -73,18 +133,29 @@ impl Permissions {
         resource: impl Into<Box<dyn Resource>>,
     ) -> Result<()> {
         let permission = &permission.into();
-        let permission_key = &permission.get_key();
         let resource = &resource.into();
 
-        // Check permission type exists.
-        match self.map.get(permission_key) {
-            None => errors::permission_error_t(format!(
-                r#"permission type "{}" does not exist for file {:?}"#,
-                permission.get_type(),
-                resource
-            )),
-            Some(allow_list) => permission.check(&resource, Rc::clone(allow_list), &self.state),
+        let (result, deny_entry) = self.decide(permission, resource);
+
+        self.notify_observers(|| PermissionCheck {
+            permission_type: permission.get_type(),
+            resource: Some(resource.clone()),
+            allowed: result.is_ok(),
+            allow_entry: result.as_ref().ok().cloned().flatten(),
+            deny_entry,
+        });
+
+        // SEC: In learning mode, violations are logged and recorded instead of denied.
+        if let Some(learner) = &self.learner {
+            learner.record(permission.get_key(), Some(resource), &self.state);
+
+            if let Err(err) = &result {
+                warn!("Permission violation allowed in learning mode: {}", err);
+                return Ok(());
+            }
         }
+
+        result.map(|_| ())
     }
 
     pub fn check_exists(&self, permission: impl Into<Box<dyn PermissionType>>) -> Result<()> {
@@ -92,7 +163,30 @@ impl Permissions {
         let permission_key = &permission.get_key();
 
         // Check permission type exists.
-        if let None = self.map.get(permission_key) {
+        let allowed = self.exists(permission_key);
+
+        self.notify_observers(|| PermissionCheck {
+            permission_type: permission.get_type(),
+            resource: None,
+            allowed,
+            allow_entry: None,
+            deny_entry: None,
+        });
+
+        // SEC: In learning mode, violations are logged and recorded instead of denied.
+        if let Some(learner) = &self.learner {
+            learner.record(permission.get_key(), None, &self.state);
+
+            if !allowed {
+                warn!(
+                    r#"Permission violation allowed in learning mode: permission type "{}" does not exist"#,
+                    permission.get_type()
+                );
+                return Ok(());
+            }
+        }
+
+        if !allowed {
             return errors::permission_error_t(format!(
                 r#"permission type "{}" does not exist"#,
                 permission.get_type(),
@@ -101,18 +195,277 @@ impl Permissions {
 
         Ok(())
     }
+
+    /// Checks that no deny entry of the permission types may match `resource` or a resource nested under it, e.g. before a
+    /// dir is moved or removed with its content.
+    ///
+    /// SEC: Entries that may match count as matches, so this can deny more than checking each nested resource would.
+    pub fn check_nested(
+        &self,
+        permissions: &[impl Into<Box<dyn PermissionType>> + Clone],
+        resource: impl Into<Box<dyn Resource>>,
+    ) -> Result<()> {
+        let resource = &resource.into();
+
+        for permission in permissions.iter() {
+            let permission: &Box<dyn PermissionType> = &permission.clone().into();
+            let (result, deny_entry) = self.decide_nested(permission, resource);
+
+            self.notify_observers(|| PermissionCheck {
+                permission_type: permission.get_type(),
+                resource: Some(resource.clone()),
+                allowed: result.is_ok(),
+                allow_entry: None,
+                deny_entry,
+            });
+
+            // SEC: In learning mode, violations are logged instead of denied.
+            if let (Some(_), Err(err)) = (&self.learner, &result) {
+                warn!("Permission violation allowed in learning mode: {}", err);
+                continue;
+            }
+
+            result?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks if a permission is granted, optionally for a resource, without denying anything.
+    ///
+    /// Queries are not observed or learnt.
+    pub fn query(
+        &self,
+        permission: impl Into<Box<dyn PermissionType>>,
+        resource: Option<Box<dyn Resource>>,
+    ) -> bool {
+        let permission = &permission.into();
+
+        match &resource {
+            Some(resource) => self.decide(permission, resource).0.is_ok(),
+            None => self.exists(&permission.get_key()),
+        }
+    }
+
+    /// Decides a check without notifying observers or learning. Also gets the deny entry that matches, if any.
+    fn decide(
+        &self,
+        permission: &Box<dyn PermissionType>,
+        resource: &Box<dyn Resource>,
+    ) -> (Result<Option<Box<dyn Resource>>>, Option<Box<dyn Resource>>) {
+        let permission_key = &permission.get_key();
+
+        // SEC: Check deny entries first as they always beat allow entries.
+        let deny_entry = match self.deny_map.get(permission_key) {
+            Some(deny_list) => permission.find_match(&resource, deny_list, &self.state),
+            None => Ok(None),
+        };
+
+        let result = match &deny_entry {
+            Err(err) => errors::permission_error_t(format!(
+                r#"checking deny entries of permission type "{}" for {:?}, {}"#,
+                permission.get_type(),
+                resource,
+                err
+            )),
+            Ok(Some(deny_entry)) => errors::permission_error_t(format!(
+                r#"permission type "{}" is denied for {:?} by {:?}"#,
+                permission.get_type(),
+                resource,
+                deny_entry
+            )),
+            // Check permission type exists.
+            Ok(None) => match self.map.get(permission_key) {
+                None => errors::permission_error_t(format!(
+                    r#"permission type "{}" does not exist for file {:?}"#,
+                    permission.get_type(),
+                    resource
+                )),
+                Some(allow_list) => match self.check_grant(permission_key) {
+                    Err(err) => errors::permission_error_t(format!(
+                        r#"permission type "{}" is no longer granted, {}"#,
+                        permission.get_type(),
+                        err
+                    )),
+                    Ok(()) => {
+                        permission.check_allowed(&resource, Arc::clone(allow_list), &self.state)
+                    }
+                },
+            },
+        };
+
+        let deny_entry = deny_entry.ok().flatten();
+        if result.is_err() {
+            return (result, deny_entry);
+        }
+
+        // SEC: Bounds have the final say.
+        for bound in self.bounds.iter() {
+            let (bound_result, bound_deny_entry) = bound.decide(permission, resource);
+            if let Err(err) = bound_result {
+                return (
+                    errors::permission_error_t(format!(
+                        r#"permission type "{}" exceeds the bounds of the permissions for {:?}, {}"#,
+                        permission.get_type(),
+                        resource,
+                        err
+                    )),
+                    bound_deny_entry,
+                );
+            }
+        }
+
+        (result, deny_entry)
+    }
+
+    /// Same as `decide` but for resources nested under the resource. Only deny entries are checked.
+    fn decide_nested(
+        &self,
+        permission: &Box<dyn PermissionType>,
+        resource: &Box<dyn Resource>,
+    ) -> (Result<()>, Option<Box<dyn Resource>>) {
+        let deny_entry = match self.deny_map.get(&permission.get_key()) {
+            Some(deny_list) => permission.find_match_under(&resource, deny_list, &self.state),
+            None => Ok(None),
+        };
+
+        match deny_entry {
+            Err(err) => (
+                errors::permission_error_t(format!(
+                    r#"checking deny entries of permission type "{}" under {:?}, {}"#,
+                    permission.get_type(),
+                    resource,
+                    err
+                )),
+                None,
+            ),
+            Ok(Some(deny_entry)) => (
+                errors::permission_error_t(format!(
+                    r#"permission type "{}" may be denied under {:?} by {:?}"#,
+                    permission.get_type(),
+                    resource,
+                    deny_entry
+                )),
+                Some(deny_entry),
+            ),
+            Ok(None) => {
+                // SEC: Bounds have the final say.
+                for bound in self.bounds.iter() {
+                    let (bound_result, bound_deny_entry) =
+                        bound.decide_nested(permission, resource);
+                    if bound_result.is_err() {
+                        return (bound_result, bound_deny_entry);
+                    }
+                }
+
+                (Ok(()), None)
+            }
+        }
+    }
+
+    fn exists(&self, permission_key: &PermissionTypeKey) -> bool {
+        self.map.contains_key(permission_key)
+            && self.check_grant(permission_key).is_ok()
+            && self.bounds.iter().all(|bound| bound.exists(permission_key))
+    }
+
+    fn check_grant(&self, permission_key: &PermissionTypeKey) -> Result<()> {
+        match self.grants.get(permission_key) {
+            Some(grant) => grant.check(),
+            None => Ok(()),
+        }
+    }
+
+    fn notify_observers(&self, get_check: impl FnOnce() -> PermissionCheck) {
+        // Avoid cloning resources when nothing is observing.
+        if self.observers.is_empty() {
+            return;
+        }
+
+        let check = get_check();
+        for observer in self.observers.iter() {
+            observer.observe(&check);
+        }
+    }
+}
+
+impl StateMap {
+    pub fn insert(&mut self, state: impl Into<Box<dyn State>>) {
+        let state = state.into();
+
+        // NOTE: Deref first so that the type id is that of the state and not of the box.
+        let type_id = (*state).as_any().type_id();
+        self.0.insert(type_id, Arc::from(state));
+    }
+
+    pub fn get<S: State>(&self) -> Option<&S> {
+        self.0
+            .get(&TypeId::of::<S>())
+            .and_then(|state| state.downcast_ref::<S>())
+    }
+
+    /// Adds the states of `other` whose type is missing.
+    pub fn fill(&mut self, other: &StateMap) {
+        for (type_id, state) in other.0.iter() {
+            self.0.entry(*type_id).or_insert_with(|| Arc::clone(state));
+        }
+    }
+
+    /// Same as `get` but a missing state is an error.
+    pub fn try_get<S: State>(&self) -> Result<&S> {
+        match self.get::<S>() {
+            Some(state) => Ok(state),
+            None => {
+                errors::permission_error_t(format!(r#"state "{}" not specified"#, type_name::<S>()))
+            }
+        }
+    }
 }
 
 impl PermissionsBuilder {
     pub fn new() -> Self {
         Self {
             map: BTreeMap::new(),
-            state: None,
+            deny_map: BTreeMap::new(),
+            state: StateMap::default(),
+            observers: vec![],
+            learner: None,
+            grants: BTreeMap::new(),
         }
     }
 
+    /// Adds the state of a permission type. A state of the same type is replaced.
     pub fn add_state(mut self, state: impl Into<Box<dyn State>>) -> Self {
-        self.state = Some(state.into());
+        self.state.insert(state);
+        self
+    }
+
+    /// Turns on learning mode. Checks that would be denied are allowed and every permission used is recorded by the learner.
+    pub fn learn(mut self, learner: Arc<PermissionLearner>) -> Self {
+        self.learner = Some(learner);
+        self
+    }
+
+    /// Adds an observer that is notified of every permission check.
+    pub fn add_observer(mut self, observer: Arc<dyn PermissionObserver>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// Attaches conditions like an expiry to the grants of permission types. Conditions added for the same type all apply.
+    pub fn add_grant(
+        mut self,
+        permissions: &[impl Into<Box<dyn PermissionType>> + Clone],
+        grant: Grant,
+    ) -> Self {
+        for permission_type in permissions.iter() {
+            let permission_type: Box<dyn PermissionType> = permission_type.clone().into();
+            self.grants
+                .entry(permission_type.get_key())
+                .or_default()
+                .merge(&grant);
+        }
+
         self
     }
 
@@ -135,7 +488,7 @@ impl PermissionsBuilder {
             let allow_list = permission_type.map(allow_list, &self.state)?;
 
             // Add permission type.
-            self.map.insert(permission_key, Rc::new(allow_list));
+            self.map.insert(permission_key, Arc::new(allow_list));
         }
 
         Ok(self)
@@ -157,7 +510,46 @@ impl PermissionsBuilder {
             let allow_list = permission_type.map(allow_list, &self.state)?;
 
             // Add permission type.
-            self.map.insert(permission_key, Rc::new(allow_list));
+            self.map.insert(permission_key, Arc::new(allow_list));
+        }
+
+        Ok(self)
+    }
+
+    /// Adds deny entries. A resource matching a deny entry is denied even if it is in the allow list.
+    pub fn add_permissions_with_deny_lists(
+        self,
+        permissions: &[(
+            impl Into<Box<dyn PermissionType>> + Clone,
+            &[impl Into<Box<dyn Resource>> + Clone],
+        )],
+    ) -> Result<Self> {
+        let permissions = permissions
+            .iter()
+            .map(|(permission_type, resources)| {
+                (
+                    permission_type.clone().into(),
+                    resources.iter().map(|s| s.clone().into()).collect(),
+                )
+            })
+            .collect();
+
+        self.add_owned_permissions_with_deny_lists(permissions)
+    }
+
+    pub fn add_owned_permissions_with_deny_lists(
+        mut self,
+        permissions: Vec<(Box<dyn PermissionType>, Vec<Box<dyn Resource>>)>,
+    ) -> Result<Self> {
+        for (permission_type, deny_list) in permissions.into_iter() {
+            // Get permission key from permission type.
+            let permission_key = permission_type.get_key();
+
+            // Deny entries are mapped the same way allow entries are.
+            let deny_list = permission_type.map(deny_list, &self.state)?;
+
+            // Add deny entries.
+            self.deny_map.insert(permission_key, Arc::new(deny_list));
         }
 
         Ok(self)
@@ -173,7 +565,7 @@ impl PermissionsBuilder {
             let permission_key = permission_type.get_key();
 
             // Add permission type.
-            self.map.insert(permission_key, Rc::new(vec![]));
+            self.map.insert(permission_key, Arc::new(vec![]));
         }
 
         Ok(self)
@@ -189,7 +581,7 @@ impl PermissionsBuilder {
             let permission_key = permission_type.get_key();
 
             // Add permission type.
-            self.map.insert(permission_key, Rc::new(vec![]));
+            self.map.insert(permission_key, Arc::new(vec![]));
         }
 
         Ok(self)
@@ -198,7 +590,12 @@ impl PermissionsBuilder {
     pub fn build(self) -> Permissions {
         Permissions {
             map: self.map,
+            deny_map: self.deny_map,
             state: self.state,
+            observers: self.observers,
+            learner: self.learner,
+            grants: self.grants,
+            bounds: vec![],
         }
     }
 }
@@ -221,8 +618,14 @@ impl Clone for Box<dyn Resource> {
     }
 }
 
-impl std::fmt::Debug for Box<dyn State> {
+impl std::fmt::Debug for dyn State {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.get_debug(f)
     }
 }
+
+impl std::fmt::Debug for StateMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.0.values()).finish()
+    }
+}
@@ -12,7 +12,7 @@
     TypedArray,
     Symbol,
   } = window.__bootstrap.primordials;
-  const { UnimplementedError, LimitExceededError } = window.__bootstrap.errors;
+  const { LimitExceededError } = window.__bootstrap.errors;
   const { SIZE_PER_ITER } = window.__bootstrap.common;
 
   class BufferStream {
@@ -24,9 +24,8 @@
       return null;
     }
 
-    close() {
-      throw new UnimplementedError();
-    }
+    // Streams without underlying resources have nothing to close.
+    close() {}
 
     // Writes a buffer to destination. Writes large buffers in SIZE_PER_ITER chunks.
     async writeAll(buffer, bufferSize = SIZE_PER_ITER) {
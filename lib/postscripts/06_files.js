@@ -9,7 +9,23 @@
     return;
   }
 
-  const { fsOpen, fsRead, fsWrite } = window.__bootstrap.fs;
+  const {
+    fsOpen,
+    fsRead,
+    fsWrite,
+    fsSeek,
+    fsClose,
+    fsTruncate,
+    fsSync,
+    fsDatasync,
+    fsFstat,
+    fsStat,
+    fsReadDir,
+    fsMkdir,
+    fsRemove,
+    fsRename,
+    fsCopy,
+  } = window.__bootstrap.fs;
   const { BufferStream } = window.__bootstrap.streams;
 
   class File extends BufferStream {
@@ -42,7 +58,77 @@
     async getWriteStream() {
       return async (buffer) => await fsWrite(this.#rid, buffer);
     }
+
+    // Moves the position of the file. `whence` is 0 for the start, 1 for the current position and 2 for the end.
+    async seek(offset, whence = 0) {
+      return await fsSeek(this.#rid, { offset, whence });
+    }
+
+    close() {
+      fsClose(this.#rid);
+    }
+
+    async truncate(len = 0) {
+      await fsTruncate(this.#rid, len);
+    }
+
+    async sync() {
+      await fsSync(this.#rid);
+    }
+
+    async datasync() {
+      await fsDatasync(this.#rid);
+    }
+
+    async stat() {
+      return toFileInfo(await fsFstat(this.#rid));
+    }
+  }
+
+  function toDate(millis) {
+    return millis == null ? null : new Date(millis);
+  }
+
+  function toFileInfo(info) {
+    return {
+      ...info,
+      modified: toDate(info.modified),
+      accessed: toDate(info.accessed),
+      created: toDate(info.created),
+    };
+  }
+
+  async function stat(path) {
+    return toFileInfo(await fsStat(path));
+  }
+
+  async function readDir(path) {
+    return await fsReadDir(path);
+  }
+
+  async function mkdir(path, options = {}) {
+    await fsMkdir(path, options);
+  }
+
+  async function remove(path, options = {}) {
+    await fsRemove(path, options);
+  }
+
+  async function rename(fromPath, toPath) {
+    await fsRename(fromPath, toPath);
+  }
+
+  async function copy(fromPath, toPath) {
+    return await fsCopy(fromPath, toPath);
   }
 
-  window.__bootstrap.files = { File };
+  window.__bootstrap.files = {
+    File,
+    stat,
+    readDir,
+    mkdir,
+    remove,
+    rename,
+    copy,
+  };
 })(globalThis);
@@ -26,20 +26,29 @@
     httpGetRequestUriPathQuery,
     httpGetRequestUriHost,
     httpGetRequestUriPort,
+    httpEventDone,
   } = window.__bootstrap.httpEvent;
-  const { TypeError, Error, JSONStringify } = window.__bootstrap.primordials;
+  const { TypeError, Error, JSONStringify, ObjectDefineProperty } =
+    window.__bootstrap.primordials;
   const { Body, Response } = window.__bootstrap.http;
+  const { log } = window.__bootstrap.logger;
 
+  // Each request reads from the event with the specified ID. Null refers to the event the runtime is created with.
   class HttpEventRequest {
-    #headers = new HttpEventHeaders();
-    #uri = new HttpEventURI();
-    #method = new HttpEventMethod();
-    #version = new HttpEventVersion();
+    #headers;
+    #uri;
+    #method;
+    #version;
     #body = new Body();
 
-    constructor() {
+    constructor(eventId = null) {
+      this.#headers = new HttpEventHeaders(eventId);
+      this.#uri = new HttpEventURI(eventId);
+      this.#method = new HttpEventMethod(eventId);
+      this.#version = new HttpEventVersion(eventId);
+
       this.#body.setReadStream(() => {
-        const rid = httpGetRequestBodyReadStream(); // Creates a read stream.
+        const rid = httpGetRequestBodyReadStream(eventId); // Creates a read stream.
         return async (buffer) => await httpReadRequestBodyChunk(rid, buffer);
       });
     }
@@ -72,11 +81,16 @@
   }
 
   class HttpEventMethod {
+    #eventId;
     #cache = null;
 
+    constructor(eventId) {
+      this.#eventId = eventId;
+    }
+
     get value() {
       if (this.#cache == null) {
-        this.#cache = httpGetRequestMethod();
+        this.#cache = httpGetRequestMethod(this.#eventId);
       }
 
       return this.#cache;
@@ -88,11 +102,16 @@
   }
 
   class HttpEventVersion {
+    #eventId;
     #cache = null;
 
+    constructor(eventId) {
+      this.#eventId = eventId;
+    }
+
     get value() {
       if (this.#cache == null) {
-        this.#cache = httpGetRequestVersion();
+        this.#cache = httpGetRequestVersion(this.#eventId);
       }
 
       return this.#cache;
@@ -104,10 +123,15 @@
   }
 
   class HttpEventHeaders {
+    #eventId;
     #cache = {};
 
+    constructor(eventId) {
+      this.#eventId = eventId;
+    }
+
     get value() {
-      const kvPairs = httpGetRequestHeaders();
+      const kvPairs = httpGetRequestHeaders(this.#eventId);
       this.#cache = {
         ...kvPairs,
         ...this.#cache,
@@ -120,7 +144,7 @@
     get(key) {
       let value = this.#cache[key];
       if (value == null) {
-        value = httpGetRequestHeader(key);
+        value = httpGetRequestHeader(this.#eventId, key);
         this.#cache[key] = value;
       }
 
@@ -137,11 +161,16 @@
   }
 
   class HttpEventURI {
+    #eventId;
     #cache = {};
 
+    constructor(eventId) {
+      this.#eventId = eventId;
+    }
+
     get scheme() {
       if (this.#cache.scheme == null) {
-        this.#cache.scheme = httpGetRequestUriScheme();
+        this.#cache.scheme = httpGetRequestUriScheme(this.#eventId);
       }
 
       return this.#cache.scheme;
@@ -149,23 +178,23 @@
 
     get authority() {
       if (this.#cache.authority == null) {
-        this.#cache.authority = httpGetRequestUriAuthority();
+        this.#cache.authority = httpGetRequestUriAuthority(this.#eventId);
       }
 
       return this.#cache.authority;
     }
 
     get path() {
-      return new HTTPEventPath();
+      return new HTTPEventPath(this.#eventId);
     }
 
     get query() {
-      return new HTTPEventQuery();
+      return new HTTPEventQuery(this.#eventId);
     }
 
     get pathQuery() {
       if (this.#cache.pathQuery == null) {
-        this.#cache.pathQuery = httpGetRequestUriPathQuery();
+        this.#cache.pathQuery = httpGetRequestUriPathQuery(this.#eventId);
       }
 
       return this.#cache.pathQuery;
@@ -173,7 +202,7 @@
 
     get host() {
       if (this.#cache.host == null) {
-        this.#cache.host = httpGetRequestUriHost();
+        this.#cache.host = httpGetRequestUriHost(this.#eventId);
       }
 
       return this.#cache.host;
@@ -181,7 +210,7 @@
 
     get port() {
       if (this.#cache.port == null) {
-        this.#cache.port = httpGetRequestUriPort();
+        this.#cache.port = httpGetRequestUriPort(this.#eventId);
       }
 
       return this.#cache.port;
@@ -198,11 +227,16 @@
   }
 
   class HTTPEventPath {
+    #eventId;
     #cache = null;
 
+    constructor(eventId) {
+      this.#eventId = eventId;
+    }
+
     get value() {
       if (this.#cache == 0) {
-        this.#cache = httpGetRequestUriPath();
+        this.#cache = httpGetRequestUriPath(this.#eventId);
       }
 
       return this.#cache;
@@ -211,7 +245,7 @@
     // Takes a string as key. Returns null if param is not found.
     get(key) {
       if (this.#cache == null) {
-        this.#cache = httpGetRequestUriPath();
+        this.#cache = httpGetRequestUriPath(this.#eventId);
       }
 
       if (key.includes("/")) {
@@ -237,11 +271,16 @@
   }
 
   class HTTPEventQuery {
+    #eventId;
     #cache = 0; // Null is a valid value. So we are using integer here to represent the initial state.
 
+    constructor(eventId) {
+      this.#eventId = eventId;
+    }
+
     get value() {
       if (this.#cache == 0) {
-        this.#cache = httpGetRequestUriQuery();
+        this.#cache = httpGetRequestUriQuery(this.#eventId);
       }
 
       return this.#cache;
@@ -250,7 +289,7 @@
     // Returns empty string if the key has no value. Returns null if there is no key at all.
     get(key) {
       if (this.#cache == 0) {
-        this.#cache = httpGetRequestUriQuery();
+        this.#cache = httpGetRequestUriQuery(this.#eventId);
 
         if (this.#cache == null) {
           return null;
@@ -279,9 +318,9 @@
     }
   }
 
-  function setWriteStream(response) {
+  function setWriteStream(eventId, response) {
     response.body.setWriteStream(async () => {
-      const rid = await httpSetSendResponseBodyWriteStream(); // Creates a write stream.
+      const rid = await httpSetSendResponseBodyWriteStream(eventId); // Creates a write stream.
 
       return async (buffer) => {
         await httpWriteResponseBodyChunk(rid, buffer);
@@ -289,19 +328,16 @@
     });
   }
 
-  let request = new HttpEventRequest();
-
-  const http = {
-    request,
-    respondWith: async function (response) {
+  function createRespondWith(eventId) {
+    return async function (response) {
       // TODO(appcypher): Send Response as a single chunk. httpSetResponseParts.
       // Response object must be of type Response.
-      if (!response instanceof Response) {
+      if (!(response instanceof Response)) {
         throw new TypeError("expected response to be Response instance");
       }
 
       // Set response parts.
-      httpSetResponseParts({
+      httpSetResponseParts(eventId, {
         status: response.status,
         version: response.version,
         headers: response.headers.value,
@@ -312,19 +348,80 @@
         case "file":
         case "asyncIterator": {
           // If the write type is file or asyncIterator, we stream the content. This is transfer encoding chunked in Http/1.1, Body(Streaming) in hyper.
-          setWriteStream(response);
+          setWriteStream(eventId, response);
 
           // Drive the response body stream.
           await response.body.writeAll(response.body.writeObject);
           break;
         }
         default: {
-          await httpSetSendResponseBody(response.body.writeObject);
+          await httpSetSendResponseBody(eventId, response.body.writeObject);
         }
       }
+    };
+  }
+
+  // The listener handles events dispatched by the host to a long-lived runtime.
+  let listener = null;
+
+  // Each dispatched event gets its own request and responder. Events are handled concurrently.
+  // Nothing awaits the returned promise, so listener errors are handled here.
+  async function dispatchHttpEvent(eventId) {
+    const respondWith = createRespondWith(eventId);
+    let responded = false;
+
+    try {
+      if (listener == null) {
+        throw new Error("no HTTP event listener registered");
+      }
+
+      await listener({
+        request: new HttpEventRequest(eventId),
+        respondWith: async (response) => {
+          responded = response instanceof Response;
+          await respondWith(response);
+        },
+      });
+    } catch (error) {
+      log.error("HTTP event listener failed:", error);
+
+      // Answer the request if the listener did not get to it.
+      if (!responded) {
+        try {
+          await respondWith(
+            new Response("Internal Server Error", { status: 500 }),
+          );
+        } catch (responseError) {
+          log.error("sending error response failed:", responseError);
+        }
+      }
+    } finally {
+      httpEventDone(eventId);
+    }
+  }
+
+  const http = {
+    request: new HttpEventRequest(),
+    respondWith: createRespondWith(null),
+    listen: function (handler) {
+      if (typeof handler !== "function") {
+        throw new TypeError("expected handler to be a function");
+      }
+
+      if (listener != null) {
+        throw new Error("an HTTP event listener is already registered");
+      }
+
+      listener = handler;
     },
   };
 
+  // SEC: Taken off the global object by the runtime before any user code runs.
+  ObjectDefineProperty(globalThis, "__dispatchHttpEvent", {
+    value: dispatchHttpEvent,
+    configurable: true,
+  });
+
   const events = { http };
 
   window.__bootstrap.events = { events };
@@ -7,8 +7,20 @@ Object.defineProperty(Object.prototype, "__proto__", { set: void 0 });
 
 ((window) => {
   const { ObjectFreeze } = window.__bootstrap.primordials;
-  const { core, files, events, errors, logger, encoding, http, __custom } =
-    window.__bootstrap;
+  const {
+    core,
+    files,
+    events,
+    errors,
+    logger,
+    encoding,
+    http,
+    permissions,
+    env,
+    cache,
+    crypto,
+    __custom,
+  } = window.__bootstrap;
 
   // Register errors.
   core.registerErrorClass("NotSupported", errors.NotSupported);
@@ -26,12 +38,34 @@ Object.defineProperty(Object.prototype, "__proto__", { set: void 0 });
     log: logger.log,
     encode: encoding.encode,
     decode: encoding.decode,
-    Response: http.Response,
+    Response: http && http.Response,
     File: files && files.File,
+    fs:
+      files &&
+      ObjectFreeze({
+        stat: files.stat,
+        readDir: files.readDir,
+        mkdir: files.mkdir,
+        remove: files.remove,
+        rename: files.rename,
+        copy: files.copy,
+      }),
     events: events && events.events,
-    ...__custom, // Custom extensions.
+    permissions: permissions && ObjectFreeze({ query: permissions.query }),
+    env: env && ObjectFreeze({ get: env.envGet }),
+    cache: cache && ObjectFreeze({ get: cache.cacheGet }),
+    crypto: crypto && ObjectFreeze({ createHmac: crypto.cryptoCreateHmac }),
   };
 
+  // Add custom extensions. SEC: They must not replace built-in APIs.
+  for (const [name, value] of Object.entries(__custom ?? {})) {
+    if (name in Tera) {
+      throw new Error(`custom namespace "${name}" clashes with a built-in one`);
+    }
+
+    Tera[name] = value;
+  }
+
   // Attach namespace to global.
   globalThis.Tera = Tera;
 
@@ -1,7 +1,17 @@
 // Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
 
+mod builder;
+mod builtins;
 mod options;
+mod postscripts;
 mod runtime;
+mod snapshot;
+mod snapshot_key;
+mod watchdog;
 
+pub use builder::*;
+pub use builtins::*;
 pub use options::*;
+pub use postscripts::*;
 pub use runtime::*;
+pub use snapshot::*;
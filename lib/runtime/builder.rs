@@ -0,0 +1,170 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use super::{
+    BuiltinExtension, Postscripts, Runtime, RuntimeLimits, RuntimeOptions, SnapshotPolicy,
+};
+use crate::{
+    events::Events,
+    extensions, loaders,
+    loaders::ModuleLoader,
+    permissions::{PermissionRegistry, Permissions},
+};
+use deno_core::Extension;
+use std::{cell::RefCell, rc::Rc};
+use utilities::{errors, result::Result};
+
+/// Creates an extension with the permissions of the runtime being built.
+type ExtensionFn = Box<dyn FnOnce(Rc<RefCell<Permissions>>) -> Extension>;
+
+pub struct RuntimeBuilder {
+    permissions: Permissions,
+    permission_registry: PermissionRegistry,
+    events: Option<Rc<RefCell<Events>>>,
+    snapshot_policy: SnapshotPolicy,
+    postscripts: Postscripts,
+    limits: RuntimeLimits,
+    builtin_extensions: Vec<BuiltinExtension>,
+    extensions: Vec<ExtensionFn>,
+    options: RuntimeOptions,
+}
+
+impl RuntimeBuilder {
+    pub fn new() -> Self {
+        Self {
+            permissions: Permissions::default(),
+            permission_registry: PermissionRegistry::default(),
+            events: None,
+            snapshot_policy: SnapshotPolicy::default(),
+            postscripts: Postscripts::default(),
+            limits: RuntimeLimits::default(),
+            builtin_extensions: vec![],
+            extensions: vec![],
+            options: RuntimeOptions::default(),
+        }
+    }
+
+    pub fn permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Sets the permission types that scripts can query with `Tera.permissions.query`.
+    pub fn permission_registry(mut self, permission_registry: PermissionRegistry) -> Self {
+        self.permission_registry = permission_registry;
+        self
+    }
+
+    /// Sets the events handled by the `EventHttp` extension.
+    pub fn events(mut self, events: Rc<RefCell<Events>>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    pub fn snapshot_policy(mut self, snapshot_policy: impl Into<SnapshotPolicy>) -> Self {
+        self.snapshot_policy = snapshot_policy.into();
+        self
+    }
+
+    pub fn postscripts(mut self, postscripts: Postscripts) -> Self {
+        self.postscripts = postscripts;
+        self
+    }
+
+    pub fn limits(mut self, limits: RuntimeLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn add_builtin_extensions(mut self, extensions: &[BuiltinExtension]) -> Self {
+        self.builtin_extensions.extend_from_slice(extensions);
+        self
+    }
+
+    /// Adds a third-party extension. It is loaded after the built-in extensions.
+    pub fn add_extension(
+        mut self,
+        extension: impl FnOnce(Rc<RefCell<Permissions>>) -> Extension + 'static,
+    ) -> Self {
+        self.extensions.push(Box::new(extension));
+        self
+    }
+
+    /// Sets the underlying deno runtime options.
+    ///
+    /// Extensions specified here are loaded after every other extension, and the module loader replaces the default ESM loader.
+    pub fn options(mut self, options: RuntimeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub async fn build(self) -> Result<Runtime> {
+        let permissions = Rc::new(RefCell::new(self.permissions));
+
+        // Permission queries are always available.
+        let mut extensions = vec![extensions::permissions(
+            Rc::clone(&permissions),
+            Rc::new(self.permission_registry),
+        )];
+
+        // Built-in extensions are loaded in a fixed order so that the same selection always gives the same snapshot.
+        for builtin in BuiltinExtension::all() {
+            if !self.builtin_extensions.contains(builtin) {
+                continue;
+            }
+
+            let permissions = Rc::clone(&permissions);
+            let extension = match builtin {
+                BuiltinExtension::Fs => extensions::fs(permissions),
+                BuiltinExtension::EventHttp => match &self.events {
+                    Some(events) => extensions::event_http(permissions, Rc::clone(events)),
+                    None => {
+                        return errors::new_error_t(
+                            "the event_http extension requires events to be specified",
+                        )
+                    }
+                },
+                BuiltinExtension::Env => extensions::env(permissions),
+                BuiltinExtension::Cache => extensions::cache(permissions),
+                BuiltinExtension::Crypto => extensions::crypto(permissions),
+            };
+
+            extensions.push(extension);
+        }
+
+        // Third-party extensions.
+        for extension in self.extensions {
+            extensions.push(extension(Rc::clone(&permissions)));
+        }
+
+        let mut options = self.options;
+        extensions.append(&mut options.extensions);
+
+        // Use the default ESM loader unless a module loader is specified.
+        let module_loader: Rc<dyn ModuleLoader> = match options.module_loader.take() {
+            Some(module_loader) => module_loader,
+            None => Rc::new(loaders::esm(Rc::clone(&permissions))),
+        };
+
+        // Set runtime options
+        let options = RuntimeOptions {
+            module_loader: Some(module_loader),
+            extensions,
+            ..options
+        };
+
+        Runtime::new(
+            permissions,
+            self.snapshot_policy,
+            self.postscripts,
+            self.limits,
+            options,
+        )
+        .await
+    }
+}
+
+impl Default for RuntimeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,45 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+//! Shared with the build script, so it must not depend on anything else in the crate.
+
+/// Tera's built-in extensions.
+///
+/// Built-in extensions are always loaded in the order they are declared here regardless of the order they are selected in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinExtension {
+    Fs,
+    EventHttp,
+    Env,
+    Cache,
+    Crypto,
+}
+
+/// The built-in extensions set up by `Runtime::with_permissions`.
+pub(crate) const WITH_PERMISSIONS_EXTENSIONS: &[BuiltinExtension] = &[BuiltinExtension::Fs];
+
+/// The built-in extensions set up by `Runtime::with_events`.
+pub(crate) const WITH_EVENTS_EXTENSIONS: &[BuiltinExtension] =
+    &[BuiltinExtension::Fs, BuiltinExtension::EventHttp];
+
+impl BuiltinExtension {
+    pub fn all() -> &'static [BuiltinExtension] {
+        &[
+            Self::Fs,
+            Self::EventHttp,
+            Self::Env,
+            Self::Cache,
+            Self::Crypto,
+        ]
+    }
+
+    /// Gets the path of the script the extension loads, relative to the crate root.
+    pub fn script_path(&self) -> &'static str {
+        // Same paths as the extensions pass to `include_js_files!`.
+        match self {
+            Self::Fs => "lib/extensions/fs/01_fs.js",
+            Self::EventHttp => "lib/extensions/event_http/01_event_http.js",
+            Self::Env => "lib/extensions/env/01_env.js",
+            Self::Cache => "lib/extensions/cache/01_cache.js",
+            Self::Crypto => "lib/extensions/crypto/01_crypto.js",
+        }
+    }
+}
@@ -1,4 +1,19 @@
 // Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
 
+use std::time::Duration;
+
 // Re-export
 pub use deno_core::RuntimeOptions;
+
+/// Resource limits enforced on a runtime's isolate.
+///
+/// Limits are per runtime. Exceeding them terminates only the offending isolate.
+#[derive(Debug, Default, Clone)]
+pub struct RuntimeLimits {
+    /// The maximum size of the V8 heap in bytes.
+    pub max_heap_size: Option<usize>,
+    /// The maximum time an execution can take, including time spent waiting on ops.
+    pub wall_time: Option<Duration>,
+    /// The maximum CPU time an execution can use on the runtime's thread. Only supported on Linux.
+    pub cpu_time: Option<Duration>,
+}
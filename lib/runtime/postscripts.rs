@@ -0,0 +1,144 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use crate::{include_js_files, JsFile};
+use std::{fs, path::PathBuf};
+use utilities::{
+    errors,
+    result::{Context, Result},
+};
+
+/// Selects the postscripts a runtime executes on startup.
+///
+/// Postscripts are executed in the order of their file names, so a custom postscript like `30_globals.js` runs between the built-in ones.
+///
+/// Built-in postscripts build on the ones before them and `99_namepace.js` exposes them on the frozen `Tera` global.
+/// Skipping a built-in postscript may require skipping or replacing the ones that depend on it.
+#[derive(Debug, Clone, Default)]
+pub struct Postscripts {
+    skip_builtins: bool,
+    skipped: Vec<String>,
+    custom: Vec<(Option<String>, PathBuf)>,
+}
+
+impl Postscripts {
+    /// Runs all built-in postscripts and no custom one.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Skips every built-in postscript.
+    pub fn skip_builtins(mut self) -> Self {
+        self.skip_builtins = true;
+        self
+    }
+
+    /// Skips the built-in postscript with the specified file name, e.g. `07_http.js`.
+    pub fn skip(mut self, file_name: impl Into<String>) -> Self {
+        self.skipped.push(file_name.into());
+        self
+    }
+
+    /// Adds a custom postscript. It is ordered by its own file name.
+    pub fn add(mut self, path: impl Into<PathBuf>) -> Self {
+        self.custom.push((None, path.into()));
+        self
+    }
+
+    /// Runs a custom postscript in place of the built-in postscript with the specified file name.
+    ///
+    /// Replacing `99_namepace.js` makes it possible to build a differently shaped global in place of `Tera`.
+    pub fn replace(mut self, file_name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        let file_name = file_name.into();
+        self.skipped.push(file_name.clone());
+        self.custom.push((Some(file_name), path.into()));
+        self
+    }
+
+    /// Gets the file names of the built-in postscripts.
+    pub fn builtin_names() -> Vec<&'static str> {
+        builtin_postscripts()
+            .into_iter()
+            .map(|(name, _)| get_file_name(name))
+            .collect()
+    }
+
+    /// Gets the name and content of the selected postscripts in the order they are to be executed.
+    pub(crate) fn read(&self) -> Result<Vec<(String, String)>> {
+        let builtin_names = Self::builtin_names();
+
+        // Skipped postscripts must exist.
+        for file_name in self.skipped.iter() {
+            if !builtin_names.contains(&file_name.as_str()) {
+                return errors::new_error_t(format!(
+                    r#"unknown built-in postscript "{}", expected one of {:?}"#,
+                    file_name, builtin_names
+                ));
+            }
+        }
+
+        // Built-in postscripts are embedded in the binary.
+        let builtin_postscripts = builtin_postscripts()
+            .into_iter()
+            .filter(|(name, _)| {
+                !self.skip_builtins && !self.skipped.iter().any(|s| s == get_file_name(name))
+            })
+            .map(|(name, load)| -> Result<(String, String, String)> {
+                let content = load().context(format!(r#"loading postscript "{}""#, name))?;
+                Ok((get_file_name(name).to_string(), name.to_string(), content))
+            });
+
+        // Custom postscripts are read from disk. Replacements take the place of the postscript they replace.
+        let custom_postscripts =
+            self.custom
+                .iter()
+                .map(|(replaced, path)| -> Result<(String, String, String)> {
+                    let content = fs::read_to_string(&path)
+                        .context(format!(r#"getting postscript file, "{:?}""#, path))?;
+
+                    let file_name = match replaced {
+                        Some(file_name) => file_name.clone(),
+                        None => path
+                            .file_name()
+                            .map(|name| name.to_string_lossy().into_owned())
+                            .unwrap_or_default(),
+                    };
+
+                    Ok((file_name, format!("(tera:postscripts) {:?}", path), content))
+                });
+
+        let mut postscripts = builtin_postscripts
+            .chain(custom_postscripts)
+            .collect::<Result<Vec<_>>>()?;
+
+        // Sort postscripts by file name.
+        postscripts.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+
+        Ok(postscripts
+            .into_iter()
+            .map(|(_, name, content)| (name, content))
+            .collect())
+    }
+}
+
+/// Tera's built-in postscripts, embedded in the binary.
+///
+/// Keep in sync with `lib/postscripts`. The build script snapshots every file in there.
+pub(crate) fn builtin_postscripts() -> Vec<JsFile> {
+    include_js_files!(
+        prefix "(tera:postscripts) ",
+        "lib/postscripts/00_errors.js",
+        "lib/postscripts/01_common.js",
+        "lib/postscripts/02_encoding.js",
+        "lib/postscripts/03_timers.js",
+        "lib/postscripts/04_logger.js",
+        "lib/postscripts/05_streams.js",
+        "lib/postscripts/06_files.js",
+        "lib/postscripts/07_http.js",
+        "lib/postscripts/08_events.js",
+        "lib/postscripts/99_namepace.js",
+    )
+}
+
+fn get_file_name(name: &'static str) -> &'static str {
+    name.rsplit('/').next().unwrap_or(name)
+}
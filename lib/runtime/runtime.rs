@@ -1,133 +1,359 @@
 // Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
 
-use crate::{events::Events, extensions, loaders, permissions::Permissions, RuntimeOptions};
+use super::{
+    builder::RuntimeBuilder,
+    builtins::{WITH_EVENTS_EXTENSIONS, WITH_PERMISSIONS_EXTENSIONS},
+    postscripts::Postscripts,
+    snapshot::SnapshotPolicy,
+    snapshot_key::SnapshotKey,
+    watchdog::Watchdog,
+};
+use crate::{
+    errors::{JsError, TimeoutError},
+    events::{Events, HttpEvent, HttpEventId},
+    extensions::{self, LeakedFile},
+    permissions::Permissions,
+    JsFile, RuntimeLimits, RuntimeOptions,
+};
 use deno_core::{
-    anyhow::Error,
-    parking_lot::Mutex,
+    serde_v8,
     v8::{self, Global, Value},
-    Extension, JsRuntime, Snapshot,
+    Extension, JsRuntime, ModuleId, Snapshot,
 };
 use log::{debug, info};
-use regex::Regex;
-use std::fs;
-use std::{cell::RefCell, path::PathBuf, rc::Rc};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    cell::{Cell, RefCell},
+    convert::TryFrom,
+    future::Future,
+    path::PathBuf,
+    rc::Rc,
+    time::Duration,
+};
 use utilities::{
     errors,
     result::{Context, Result},
 };
 
+// The heap room given to a terminated isolate to unwind in.
+const HEAP_LIMIT_HEADROOM: usize = 16 * 1024 * 1024;
+
 pub struct Runtime {
     runtime: JsRuntime,
     permissions: Rc<RefCell<Permissions>>,
+    limits: RuntimeLimits,
+    heap_limit_reached: Rc<Cell<bool>>,
+    http_dispatcher: Option<Global<v8::Function>>, // Set up by the events postscript.
+    leaked_files: Vec<LeakedFile>,
+    embedded_snapshot: bool,
 }
 
 impl Runtime {
     pub async fn new(
         permissions: Rc<RefCell<Permissions>>,
-        enable_snapshot: bool,
-        custom_postscripts: Vec<PathBuf>,
+        snapshot_policy: impl Into<SnapshotPolicy>,
+        postscripts: Postscripts,
+        limits: RuntimeLimits,
         mut options: RuntimeOptions,
     ) -> Result<Self> {
-        // TODO(appcypher): SEC: Support specifying maximum_heap_size_in_bytes.
-        // TODO(appcypher): SEC: Add a callback that panics for near_heap_limit_callback.
+        let snapshot_policy = snapshot_policy.into();
 
         // Check if there is a startup snapshot.
         let has_startup_snapshot = options.startup_snapshot.is_some();
 
-        debug!("Snapshot enabled = {}", enable_snapshot);
+        debug!("Snapshot policy = {:?}", snapshot_policy);
         debug!("Snapshot available = {}", has_startup_snapshot);
 
-        // We create a new snapshot if snapshot is enabled but does not yet exist.
-        if enable_snapshot && !has_startup_snapshot {
-            debug!("Creating a snapshot runtime");
-
-            // Get options tailored for snapshot.
-            let snapshot_options = Self::create_snapshot_options(&options);
-
-            // Create a temp runtime to prevent panic in the main runtime after creating a snapshot.
-            let mut snapshot_runtime = JsRuntime::new(snapshot_options);
-
-            // Execute postscripts and create snapshot.
-            Self::execute_postscripts(&mut snapshot_runtime, custom_postscripts.clone())?;
-            Self::create_snapshot(&mut snapshot_runtime)?;
+        // Get postscripts.
+        let postscripts = postscripts.read()?;
+
+        // We get a snapshot from the cache or create a new one if snapshot is enabled but not provided.
+        let mut embedded_snapshot = false;
+        if snapshot_policy.is_enabled() && !has_startup_snapshot {
+            // Get scripts loaded by the extensions.
+            let extension_scripts = Self::read_extension_scripts(&options)?;
+
+            // Snapshot is keyed by every script it contains.
+            let snapshot_key = SnapshotKey::new(
+                extension_scripts
+                    .iter()
+                    .map(|(name, content)| (*name, content.as_str()))
+                    .chain(
+                        postscripts
+                            .iter()
+                            .map(|(name, content)| (name.as_str(), content.as_str())),
+                    ),
+            );
+
+            embedded_snapshot = SnapshotPolicy::get_embedded(&snapshot_key).is_some();
+
+            let snapshot = match snapshot_policy.get(&snapshot_key)? {
+                Some(snapshot) => snapshot,
+                None => {
+                    let snapshot = Self::create_snapshot(extension_scripts, &postscripts)?;
+                    snapshot_policy.put(&snapshot_key, snapshot)?
+                }
+            };
+
+            // Update options with the snapshot.
+            options.startup_snapshot = Some(Snapshot::Static(snapshot));
+        }
 
-            // Update options with the new snapshot.
-            options.startup_snapshot = Some(Snapshot::Boxed(Self::get_snapshot()));
+        // SEC: Set the heap ceiling of the main runtime's isolate.
+        if let Some(max_heap_size) = limits.max_heap_size {
+            debug!("Maximum heap size = {}", max_heap_size);
 
-            debug!("Dropping temp snapshot runtime");
+            let create_params = options.create_params.take().unwrap_or_default();
+            options.create_params = Some(create_params.heap_limits(0, max_heap_size));
         }
 
         // Create main runtime.
         let mut runtime = JsRuntime::new(options);
 
-        if !enable_snapshot {
+        // SEC: Terminate the isolate when it gets close to its heap ceiling instead of letting V8 abort the process.
+        let heap_limit_reached = Rc::new(Cell::new(false));
+        if limits.max_heap_size.is_some() {
+            let isolate_handle = runtime.v8_isolate().thread_safe_handle();
+            let heap_limit_reached = Rc::clone(&heap_limit_reached);
+
+            runtime.add_near_heap_limit_callback(move |current_limit, initial_limit| {
+                // SEC: Room is only given once, so a script that keeps allocating can't keep growing the heap.
+                if heap_limit_reached.replace(true) {
+                    return current_limit;
+                }
+
+                isolate_handle.terminate_execution();
+
+                // Give V8 enough room to unwind the terminated execution.
+                initial_limit + HEAP_LIMIT_HEADROOM
+            });
+        }
+
+        if !snapshot_policy.is_enabled() {
             debug!("Runtime will not use snapshot");
-            Self::execute_postscripts(&mut runtime, custom_postscripts)?;
+            Self::execute_postscripts(&mut runtime, &postscripts)?;
         }
 
+        // Get the function that dispatches HTTP events to the listener registered by the module.
+        let http_dispatcher = Self::take_global_function(&mut runtime, "__dispatchHttpEvent");
+
         Ok(Self {
             runtime,
             permissions,
+            limits,
+            heap_limit_reached,
+            http_dispatcher,
+            leaked_files: vec![],
+            embedded_snapshot,
         })
     }
 
+    pub fn builder() -> RuntimeBuilder {
+        RuntimeBuilder::new()
+    }
+
     pub async fn with_permissions(
         permissions: Permissions,
-        enable_snapshot: bool,
-        custom_postscripts: Vec<PathBuf>,
+        snapshot_policy: impl Into<SnapshotPolicy>,
+        postscripts: Postscripts,
+        limits: RuntimeLimits,
         options: RuntimeOptions,
     ) -> Result<Self> {
-        let permissions = Rc::new(RefCell::new(permissions));
-
-        let mut startup_snapshot = None;
-        if enable_snapshot && Self::snapshot_exists() {
-            startup_snapshot = Some(Snapshot::Boxed(Self::get_snapshot()));
-        }
-
-        // Set runtime options
-        let opts = RuntimeOptions {
-            module_loader: Some(Rc::new(loaders::esm(Rc::clone(&permissions)))),
-            extensions: vec![extensions::fs(Rc::clone(&permissions))],
-            startup_snapshot,
-            ..options
-        };
-
-        Self::new(permissions, enable_snapshot, custom_postscripts, opts).await
+        Self::builder()
+            .permissions(permissions)
+            .add_builtin_extensions(WITH_PERMISSIONS_EXTENSIONS)
+            .snapshot_policy(snapshot_policy)
+            .postscripts(postscripts)
+            .limits(limits)
+            .options(options)
+            .build()
+            .await
     }
 
     pub async fn with_events(
         permissions: Permissions,
         events: Rc<RefCell<Events>>,
-        enable_snapshot: bool,
-        custom_postscripts: Vec<PathBuf>,
+        snapshot_policy: impl Into<SnapshotPolicy>,
+        postscripts: Postscripts,
+        limits: RuntimeLimits,
         options: RuntimeOptions,
     ) -> Result<Self> {
-        let permissions = Rc::new(RefCell::new(permissions));
+        Self::builder()
+            .permissions(permissions)
+            .events(events)
+            .add_builtin_extensions(WITH_EVENTS_EXTENSIONS)
+            .snapshot_policy(snapshot_policy)
+            .postscripts(postscripts)
+            .limits(limits)
+            .options(options)
+            .build()
+            .await
+    }
 
-        let mut startup_snapshot = None;
-        if enable_snapshot && Self::snapshot_exists() {
-            startup_snapshot = Some(Snapshot::Boxed(Self::get_snapshot()));
-        }
+    pub async fn execute_module(
+        &mut self,
+        abs_path_str: impl AsRef<str>,
+        module_code: impl Into<String>,
+    ) -> Result<()> {
+        self.load_module(abs_path_str, module_code).await?;
+        Ok(())
+    }
+
+    /// Loads and evaluates the main module. The returned ID can be used to call the module's exports with `call_export`.
+    pub async fn load_module(
+        &mut self,
+        abs_path_str: impl AsRef<str>,
+        module_code: impl Into<String>,
+    ) -> Result<ModuleId> {
+        self.check_heap_limit()?;
+
+        let watchdog = self.start_watchdog()?;
+
+        let wall_time = self.limits.wall_time;
+        let result =
+            Self::timeout(wall_time, self.evaluate_module(abs_path_str, module_code)).await;
+
+        self.check_limits(watchdog, result)
+    }
 
-        // Set runtime options
-        let opts = RuntimeOptions {
-            module_loader: Some(Rc::new(loaders::esm(Rc::clone(&permissions)))),
-            extensions: vec![
-                extensions::fs(Rc::clone(&permissions)),
-                extensions::event_http(Rc::clone(&permissions), events),
-            ],
-            startup_snapshot,
-            ..options
+    /// Calls a function exported by a loaded module and deserializes its result, awaiting it if it is a promise.
+    ///
+    /// `args` must serialize to a sequence, e.g. a tuple, whose elements are passed to the function as arguments.
+    pub async fn call_export<R: DeserializeOwned>(
+        &mut self,
+        module_id: ModuleId,
+        export_name: impl AsRef<str>,
+        args: impl Serialize,
+    ) -> Result<R> {
+        self.check_heap_limit()?;
+
+        let watchdog = self.start_watchdog()?;
+
+        let wall_time = self.limits.wall_time;
+        let result = Self::timeout(
+            wall_time,
+            self.evaluate_export(module_id, export_name.as_ref(), args),
+        )
+        .await;
+
+        self.check_limits(watchdog, result)
+    }
+
+    /// Dispatches an HTTP event to the listener the module registered with `Tera.events.http.listen`.
+    ///
+    /// The listener gets its own request and responder for the event. It starts handling the event right away and
+    /// continues when the event loop is driven with `run_event_loop`, so several events can be handled at the same time.
+    pub fn dispatch_http_event(&mut self, event: HttpEvent) -> Result<HttpEventId> {
+        let dispatcher = match &self.http_dispatcher {
+            Some(dispatcher) => dispatcher.clone(),
+            None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
+        };
+
+        let events = match self
+            .runtime
+            .op_state()
+            .borrow()
+            .try_borrow::<Rc<RefCell<Events>>>()
+        {
+            Some(events) => Rc::clone(events),
+            None => return errors::missing_error_t(r#"unsupported event, "HttpEvent""#),
         };
 
-        Self::new(permissions, enable_snapshot, custom_postscripts, opts).await
+        self.check_heap_limit()?;
+
+        let event_id = events.borrow_mut().add_dispatched_http(event)?;
+
+        let watchdog = self.start_watchdog()?;
+
+        // The returned promise is driven by the event loop.
+        let result = self
+            .call_function(dispatcher, "__dispatchHttpEvent", (event_id,))
+            .map(|_| event_id);
+
+        if result.is_err() {
+            events.borrow_mut().remove_dispatched_http(event_id);
+        }
+
+        self.check_limits(watchdog, result)
     }
 
-    pub async fn execute_module(
+    /// Drives dispatched events, pending ops and timers until there is nothing left to do.
+    pub async fn run_event_loop(&mut self) -> Result<()> {
+        self.check_heap_limit()?;
+
+        let watchdog = self.start_watchdog()?;
+
+        let wall_time = self.limits.wall_time;
+        let runtime = &mut self.runtime;
+        let result = Self::timeout(wall_time, async move {
+            runtime
+                .run_event_loop(false)
+                .await
+                .context("running the event loop")
+        })
+        .await;
+
+        self.check_limits(watchdog, result)
+    }
+
+    pub async fn execute_middleware_script(
+        &mut self,
+        filename: impl AsRef<str>,
+        script_code: impl AsRef<str>,
+        permissions: Permissions,
+    ) -> Result<Global<Value>> {
+        self.check_heap_limit()?;
+
+        // SEC: Narrow existing permissions so that the script never exceeds them.
+        let narrowed_permissions = self.permissions.borrow().intersection(&permissions)?;
+
+        let watchdog = self.start_watchdog()?;
+
+        // Replace existing permissions with narrowed permissions.
+        let existing_permissions = self.permissions.replace(narrowed_permissions);
+
+        // Execute script.
+        let result = self
+            .runtime
+            .execute_script(filename.as_ref(), script_code.as_ref())
+            .context("executing script");
+
+        // Revert exising permissions.
+        let _ = self.permissions.replace(existing_permissions);
+
+        self.check_limits(watchdog, result)
+    }
+
+    /// Closes the files that scripts have left open. This is done when the runtime is dropped.
+    ///
+    /// Files opened at the top level of a module may still be used by later export calls or event handlers, so the
+    /// host only calls this when it is done with them.
+    pub fn close_leaked_files(&mut self) {
+        let op_state = self.runtime.op_state();
+        let leaked_files = extensions::close_leaked_files(&mut op_state.borrow_mut());
+
+        self.leaked_files.extend(leaked_files);
+    }
+
+    /// Returns the files closed by `close_leaked_files` since the last call, so that the host can report the leaks.
+    pub fn take_leaked_files(&mut self) -> Vec<LeakedFile> {
+        std::mem::take(&mut self.leaked_files)
+    }
+
+    /// Whether the runtime started from a snapshot embedded at build time rather than one created at runtime.
+    pub fn uses_embedded_snapshot(&self) -> bool {
+        self.embedded_snapshot
+    }
+
+    pub fn handle_scope(&mut self) -> v8::HandleScope {
+        self.runtime.handle_scope()
+    }
+
+    async fn evaluate_module(
         &mut self,
         abs_path_str: impl AsRef<str>,
         module_code: impl Into<String>,
-    ) -> Result<()> {
+    ) -> Result<ModuleId> {
         let abs_path_str = abs_path_str.as_ref();
 
         // Deno does not handle relative path referer well.
@@ -173,65 +399,171 @@ impl Runtime {
             }
         };
 
-        Ok(())
+        Ok(module_id)
     }
 
-    pub async fn execute_middleware_script(
+    async fn evaluate_export<R: DeserializeOwned>(
         &mut self,
-        filename: impl AsRef<str>,
-        script_code: impl AsRef<str>,
-        permissions: Permissions,
-    ) -> Result<Global<Value>> {
-        // Replace existing permissions with new permissions.
-        let existing_permissions = self.permissions.replace(permissions);
+        module_id: ModuleId,
+        export_name: &str,
+        args: impl Serialize,
+    ) -> Result<R> {
+        let module_namespace = self
+            .runtime
+            .get_module_namespace(module_id)
+            .context("getting the module namespace")?;
 
-        // Execute script.
+        let function = self.get_export_function(module_namespace, export_name)?;
+        let value = self.call_function(function, export_name, args)?;
+
+        // Wait for the returned promise while driving the event loop.
         let value = self
             .runtime
-            .execute_script(filename.as_ref(), script_code.as_ref())
-            .context("executing script")?;
+            .resolve_value(value)
+            .await
+            .context(format!(r#"awaiting the result of "{}""#, export_name))?;
 
-        // Revert exising permissions.
-        let _ = self.permissions.replace(existing_permissions);
+        let scope = &mut self.runtime.handle_scope();
+        let value = v8::Local::new(scope, value);
 
-        Ok(value)
+        serde_v8::from_v8(scope, value)
+            .context(format!(r#"deserializing the result of "{}""#, export_name))
     }
 
-    pub fn handle_scope(&mut self) -> v8::HandleScope {
-        self.runtime.handle_scope()
+    fn get_export_function(
+        &mut self,
+        module_namespace: Global<v8::Object>,
+        export_name: &str,
+    ) -> Result<Global<v8::Function>> {
+        let scope = &mut self.runtime.handle_scope();
+        let module_namespace = v8::Local::new(scope, module_namespace);
+
+        match v8::String::new(scope, export_name)
+            .and_then(|key| module_namespace.get(scope, key.into()))
+            .map(v8::Local::<v8::Function>::try_from)
+        {
+            Some(Ok(function)) => Ok(Global::new(scope, function)),
+            _ => errors::type_error_t(format!(
+                r#"expected module export "{}" to be a function"#,
+                export_name
+            )),
+        }
+    }
+
+    fn call_function(
+        &mut self,
+        function: Global<v8::Function>,
+        function_name: &str,
+        args: impl Serialize,
+    ) -> Result<Global<Value>> {
+        let scope = &mut self.runtime.handle_scope();
+        let function = v8::Local::new(scope, function);
+
+        // Convert arguments.
+        let args = serde_v8::to_v8(scope, args).context("serializing the arguments")?;
+        let args = match v8::Local::<v8::Array>::try_from(args) {
+            Ok(args) => args,
+            Err(_) => return errors::type_error_t("expected arguments to serialize to a sequence"),
+        };
+
+        let mut arg_values = vec![];
+        for index in 0..args.length() {
+            let value = args
+                .get_index(scope, index)
+                .unwrap_or_else(|| v8::undefined(scope).into());
+
+            arg_values.push(value);
+        }
+
+        // Call the function and report exceptions as JS errors.
+        let scope = &mut v8::TryCatch::new(scope);
+        let receiver = v8::undefined(scope).into();
+        match function.call(scope, receiver, &arg_values) {
+            Some(value) => Ok(Global::new(scope, value)),
+            None => match scope.exception() {
+                Some(exception) => Err(JsError::from_v8_exception(scope, exception))
+                    .context(format!(r#"calling "{}""#, function_name)),
+                None => errors::new_error_t(format!(
+                    r#"execution of "{}" was terminated"#,
+                    function_name
+                )),
+            },
+        }
+    }
+
+    /// Takes a function a postscript left on the global object for the runtime to call.
+    ///
+    /// SEC: The function is removed from the global object so that user code can't call it.
+    fn take_global_function(runtime: &mut JsRuntime, name: &str) -> Option<Global<v8::Function>> {
+        let scope = &mut runtime.handle_scope();
+        let global = scope.get_current_context().global(scope);
+        let key = v8::String::new(scope, name)?;
+
+        let value = global.get(scope, key.into())?;
+        global.delete(scope, key.into());
+
+        let function = v8::Local::<v8::Function>::try_from(value).ok()?;
+        Some(Global::new(scope, function))
+    }
+
+    /// Fails with a wall-clock timeout if the future does not complete in time.
+    ///
+    /// SEC: This is also needed with the watchdog because a pending op never gets terminated by it.
+    async fn timeout<T>(
+        wall_time: Option<Duration>,
+        future: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        match wall_time {
+            Some(wall_time) => match tokio::time::timeout(wall_time, future).await {
+                Ok(result) => result,
+                Err(_) => Err(TimeoutError::WallTime(wall_time).into()),
+            },
+            None => future.await,
+        }
+    }
+
+    fn start_watchdog(&mut self) -> Result<Watchdog> {
+        let isolate_handle = self.runtime.v8_isolate().thread_safe_handle();
+        Watchdog::start(isolate_handle, &self.limits)
+    }
+
+    /// Reports executions terminated by the watchdog or the heap limit callback as errors distinct from JS exceptions.
+    fn check_limits<T>(&mut self, watchdog: Watchdog, result: Result<T>) -> Result<T> {
+        let timeout = watchdog.stop();
+
+        self.check_heap_limit()?;
+
+        if let Some(timeout) = timeout {
+            // Unlike the heap limit, a deadline does not leave the isolate in a bad state so it can be used again.
+            self.runtime.v8_isolate().cancel_terminate_execution();
+            return Err(timeout.into());
+        }
+
+        result
+    }
+
+    /// Reports a terminated isolate as a limit error.
+    ///
+    /// An isolate that reached its heap ceiling stays terminated, so subsequent executions fail as well.
+    fn check_heap_limit(&self) -> Result<()> {
+        if self.heap_limit_reached.get() {
+            return errors::limit_exceeded_error_t(format!(
+                "maximum heap size of {} bytes exceeded",
+                self.limits.max_heap_size.unwrap_or_default()
+            ));
+        }
+
+        Ok(())
     }
 
     fn execute_postscripts(
         runtime: &mut JsRuntime,
-        custom_postscripts: Vec<PathBuf>,
+        postscripts: &[(String, String)],
     ) -> Result<()> {
-        // TODO(appcypher): Need to make it possible for users to skip Tera's postscripts and add their own.
-        // Get postcripts directory.
-        let postscripts_dir =
-            &PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/lib/postscripts"));
-
-        // Blindly assume everything in directory is a postscript.
-        let mut postscripts = fs::read_dir(postscripts_dir)
-            .context(format!(r#"reading postcripts dir "{:?}""#, postscripts_dir))?
-            .map(|entry| -> Result<PathBuf> {
-                Ok(entry
-                    .context("collecting entries in postcripts dir")?
-                    .path())
-            })
-            .chain(custom_postscripts.into_iter().map(Ok))
-            .collect::<Result<Vec<PathBuf>>>()?;
-
-        // Sort postscripts.
-        postscripts.sort();
-
-        for path in postscripts.iter() {
-            // Read content.
-            let content = fs::read_to_string(&path)
-                .context(format!(r#"getting postscript file, "{:?}""#, path))?;
-
+        for (name, content) in postscripts.iter() {
             // Execute postscript.
             runtime
-                .execute_script(&format!("(tera:postscripts) {:?}", path), &content)
+                .execute_script(name, content)
                 .context("executing postscript file")?;
         }
 
@@ -240,65 +572,51 @@ impl Runtime {
         Ok(())
     }
 
-    fn create_snapshot_options(options: &RuntimeOptions) -> RuntimeOptions {
-        // Construct the extensions. We only need the source pairs from the extensions. core/runtime.rs#init_extension_js
-        let mut extensions: Vec<Extension> = vec![];
+    fn read_extension_scripts(options: &RuntimeOptions) -> Result<Vec<(&'static str, String)>> {
+        // We only need the source pairs from the extensions. core/runtime.rs#init_extension_js
+        let mut scripts = vec![];
         for ext in &options.extensions {
-            let mut js_files: Vec<(&'static str, Box<dyn Fn() -> Result<String, Error>>)> = vec![];
-
-            for (filepath, _) in ext.init_js() {
-                let filepath_rc = Rc::new(filepath.to_string());
+            for (filepath, load) in ext.init_js() {
+                let content =
+                    load().context(format!(r#"loading extension script "{}""#, filepath))?;
 
-                let pair: (&'static str, Box<dyn Fn() -> Result<String, Error>>) = (
-                    filepath,
-                    Box::new(move || {
-                        // Replace the file prefix.
-                        let re = Regex::new(r"^\(.+\)\s*").unwrap();
-                        let result =
-                            re.replace_all(filepath_rc.as_str(), env!("CARGO_MANIFEST_DIR"));
+                debug!("Script to be loaded into snapshot = {}", filepath);
 
-                        debug!("Script to be loaded into snapshot = {}", result);
+                scripts.push((*filepath, content));
+            }
+        }
 
-                        Ok(fs::read_to_string(result.as_ref())?)
-                    }),
-                );
+        Ok(scripts)
+    }
 
-                js_files.push(pair);
-            }
+    fn create_snapshot(
+        extension_scripts: Vec<(&'static str, String)>,
+        postscripts: &[(String, String)],
+    ) -> Result<Box<[u8]>> {
+        debug!("Creating a snapshot runtime");
 
-            extensions.push(Extension::builder().js(js_files).build())
+        // Construct an extension with the same scripts. Ops are not needed to create a snapshot.
+        let mut js_files: Vec<JsFile> = vec![];
+        for (filepath, content) in extension_scripts {
+            js_files.push((filepath, Box::new(move || Ok(content.clone()))));
         }
 
-        RuntimeOptions {
-            extensions,
+        let snapshot_options = RuntimeOptions {
+            extensions: vec![Extension::builder().js(js_files).build()],
             will_snapshot: true,
             ..Default::default()
-        }
-    }
-
-    fn create_snapshot(runtime: &mut JsRuntime) -> Result<()> {
-        let startup_data = runtime.snapshot();
-        *SNAPSHOT.lock() = startup_data.to_vec();
+        };
 
-        info!("Created a new snapshot");
+        // Create a temp runtime to prevent panic in the main runtime after creating a snapshot.
+        let mut snapshot_runtime = JsRuntime::new(snapshot_options);
 
-        Ok(())
-    }
+        // Execute postscripts and create snapshot.
+        Self::execute_postscripts(&mut snapshot_runtime, postscripts)?;
+        let snapshot = snapshot_runtime.snapshot().to_vec().into_boxed_slice();
 
-    fn get_snapshot() -> Box<[u8]> {
-        debug!("Using snapshot");
-        // TODO: Explore Snapshot::Static(...) options.
-        // Not going to be as fast as .as_ref() but it should suffice.
-        SNAPSHOT.lock().clone().into_boxed_slice()
-    }
-
-    pub fn snapshot_exists() -> bool {
-        // Caveat: Scripts loaded in snapshots are not watched for changes.
-        if SNAPSHOT.lock().len() > 0 {
-            return true;
-        }
+        info!("Created a new snapshot");
 
-        false
+        Ok(snapshot)
     }
 
     fn handle_reciever_error<T: std::error::Error + 'static + Send + Sync>(
@@ -308,6 +626,9 @@ impl Runtime {
     }
 }
 
-lazy_static! {
-    static ref SNAPSHOT: Mutex<Vec<u8>> = Mutex::new(vec![]);
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        // Files left open until now are leaked. Closing them here logs the leak.
+        self.close_leaked_files();
+    }
 }
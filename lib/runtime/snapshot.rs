@@ -0,0 +1,122 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use super::snapshot_key::SnapshotKey;
+use deno_core::parking_lot::Mutex;
+use log::debug;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use utilities::result::{Context, Result};
+
+/// Determines if and where startup snapshots are kept.
+///
+/// Snapshots are keyed by a hash of the scripts they contain, so runtimes with different postscripts or extensions never share a snapshot.
+#[derive(Debug, Clone)]
+pub enum SnapshotPolicy {
+    /// Scripts are executed on every runtime start.
+    Disabled,
+    /// Snapshots are kept in memory for the lifetime of the process.
+    InMemory,
+    /// Snapshots are kept in memory and persisted to the specified cache directory so they can be reused across restarts.
+    Persisted(PathBuf),
+}
+
+impl SnapshotPolicy {
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, Self::Disabled)
+    }
+
+    /// Gets a snapshot embedded at build time, from memory or, failing that, from the cache directory.
+    pub(crate) fn get(&self, key: &SnapshotKey) -> Result<Option<&'static [u8]>> {
+        if let Some(data) = Self::get_embedded(key) {
+            debug!("Using embedded snapshot {:?}", key);
+            return Ok(Some(data));
+        }
+
+        if let Some(data) = SNAPSHOTS.lock().get(key) {
+            debug!("Using in-memory snapshot {:?}", key);
+            return Ok(Some(*data));
+        }
+
+        if let Self::Persisted(cache_dir) = self {
+            let path = key.get_path(cache_dir);
+            if path.is_file() {
+                debug!("Using persisted snapshot {:?}", path);
+
+                let data = fs::read(&path).context(format!(r#"reading snapshot "{:?}""#, path))?;
+
+                return Ok(Some(Self::cache(key, data.into_boxed_slice())));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Gets a snapshot embedded at build time.
+    pub(crate) fn get_embedded(key: &SnapshotKey) -> Option<&'static [u8]> {
+        EMBEDDED_SNAPSHOTS
+            .iter()
+            .find(|(k, _)| *k == key.as_str())
+            .map(|(_, data)| *data)
+    }
+
+    /// Saves a snapshot in memory and, if persisted, in the cache directory.
+    pub(crate) fn put(&self, key: &SnapshotKey, data: Box<[u8]>) -> Result<&'static [u8]> {
+        if let Self::Persisted(cache_dir) = self {
+            fs::create_dir_all(cache_dir)
+                .context(format!(r#"creating snapshot cache dir "{:?}""#, cache_dir))?;
+
+            // Write to a temp file first so that other processes never read a partially written snapshot.
+            let path = key.get_path(cache_dir);
+            let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+
+            fs::write(&temp_path, &data)
+                .context(format!(r#"writing snapshot "{:?}""#, temp_path))?;
+            fs::rename(&temp_path, &path)
+                .context(format!(r#"persisting snapshot "{:?}""#, path))?;
+
+            debug!("Persisted snapshot {:?}", path);
+        }
+
+        Ok(Self::cache(key, data))
+    }
+
+    /// Cached snapshots are never evicted, so they are leaked to be used as static snapshots without copying.
+    fn cache(key: &SnapshotKey, data: Box<[u8]>) -> &'static [u8] {
+        *SNAPSHOTS
+            .lock()
+            .entry(key.clone())
+            .or_insert_with(|| Box::leak(data))
+    }
+}
+
+impl SnapshotKey {
+    fn get_path(&self, cache_dir: &Path) -> PathBuf {
+        cache_dir.join(format!("{}.snap", self.as_str()))
+    }
+}
+
+impl From<bool> for SnapshotPolicy {
+    fn from(enable_snapshot: bool) -> Self {
+        if enable_snapshot {
+            Self::InMemory
+        } else {
+            Self::Disabled
+        }
+    }
+}
+
+impl Default for SnapshotPolicy {
+    fn default() -> Self {
+        Self::Disabled
+    }
+}
+
+lazy_static! {
+    static ref SNAPSHOTS: Mutex<HashMap<SnapshotKey, &'static [u8]>> = Mutex::new(HashMap::new());
+}
+
+// Snapshots of the built-in postscripts and extensions created by the build script.
+include!(concat!(env!("OUT_DIR"), "/snapshots.rs"));
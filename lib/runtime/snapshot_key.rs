@@ -0,0 +1,35 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+//! Shared with the build script, so it must not depend on anything else in the crate.
+
+use deno_core::v8;
+use sha2::{Digest, Sha256};
+
+/// The content-addressed key of a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct SnapshotKey(String);
+
+impl SnapshotKey {
+    /// Creates a key from the name and content of every script loaded into a snapshot, in the order they are loaded.
+    ///
+    /// The tera and V8 versions are part of the key because snapshots are not portable across V8 versions.
+    pub(crate) fn new<'a>(scripts: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let mut hasher = Sha256::new();
+
+        hasher.update(env!("CARGO_PKG_VERSION"));
+        hasher.update(v8::V8::get_version());
+
+        // Lengths are hashed as well to prevent ambiguous concatenations.
+        for (name, content) in scripts {
+            hasher.update((name.len() as u64).to_le_bytes());
+            hasher.update(name);
+            hasher.update((content.len() as u64).to_le_bytes());
+            hasher.update(content);
+        }
+
+        Self(format!("{:x}", hasher.finalize()))
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        &self.0
+    }
+}
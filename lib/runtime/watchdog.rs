@@ -0,0 +1,142 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use crate::{errors::TimeoutError, RuntimeLimits};
+use deno_core::v8::IsolateHandle;
+use log::debug;
+use std::{
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use utilities::{errors, result::Result};
+
+/// How often the watchdog checks the deadlines of an execution.
+const WATCHDOG_TICK: Duration = Duration::from_millis(10);
+
+/// Terminates an isolate from a separate thread once an execution runs past its deadlines.
+///
+/// A separate thread is needed because a synchronous loop in JavaScript never yields back to the event loop.
+pub(crate) struct Watchdog {
+    stop_tx: Option<Sender<()>>,
+    handle: Option<JoinHandle<Option<TimeoutError>>>,
+}
+
+impl Watchdog {
+    /// Starts watching the execution about to happen on the current thread.
+    ///
+    /// No thread is spawned if there are no deadlines to enforce.
+    pub fn start(isolate_handle: IsolateHandle, limits: &RuntimeLimits) -> Result<Self> {
+        if limits.wall_time.is_none() && limits.cpu_time.is_none() {
+            return Ok(Self {
+                stop_tx: None,
+                handle: None,
+            });
+        }
+
+        // The CPU clock must be gotten on the thread running the isolate.
+        let cpu_budget = match limits.cpu_time {
+            Some(cpu_time) => {
+                let clock = ThreadCpuClock::current()?;
+                let cpu_started = clock.elapsed();
+                Some((cpu_time, clock, cpu_started))
+            }
+            None => None,
+        };
+
+        let wall_time = limits.wall_time;
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+
+        let handle = thread::spawn(move || {
+            let started = Instant::now();
+
+            loop {
+                // A message or a dropped sender means the execution is done.
+                match stop_rx.recv_timeout(WATCHDOG_TICK) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => return None,
+                }
+
+                let timeout = match (wall_time, &cpu_budget) {
+                    (Some(wall_time), _) if started.elapsed() >= wall_time => {
+                        Some(TimeoutError::WallTime(wall_time))
+                    }
+                    (_, Some((cpu_time, clock, cpu_started)))
+                        if clock.elapsed().saturating_sub(*cpu_started) >= *cpu_time =>
+                    {
+                        Some(TimeoutError::CpuTime(*cpu_time))
+                    }
+                    _ => None,
+                };
+
+                if let Some(timeout) = timeout {
+                    debug!("Terminating execution, {}", timeout);
+                    isolate_handle.terminate_execution();
+                    return Some(timeout);
+                }
+            }
+        });
+
+        Ok(Self {
+            stop_tx: Some(stop_tx),
+            handle: Some(handle),
+        })
+    }
+
+    /// Stops watching the execution and returns the deadline that got exceeded, if any.
+    pub fn stop(mut self) -> Option<TimeoutError> {
+        if let Some(stop_tx) = self.stop_tx.take() {
+            let _ = stop_tx.send(());
+        }
+
+        self.handle
+            .take()
+            .and_then(|handle| handle.join().ok().flatten())
+    }
+}
+
+/// The CPU-time clock of a thread.
+struct ThreadCpuClock {
+    #[cfg(target_os = "linux")]
+    clock_id: libc::clockid_t,
+}
+
+impl ThreadCpuClock {
+    #[cfg(target_os = "linux")]
+    fn current() -> Result<Self> {
+        let mut clock_id: libc::clockid_t = 0;
+
+        // SAFETY: `clock_id` is a valid pointer and `pthread_self` always returns a valid thread.
+        let code = unsafe { libc::pthread_getcpuclockid(libc::pthread_self(), &mut clock_id) };
+        if code != 0 {
+            return errors::new_error_t(format!(
+                "getting the cpu clock of the runtime thread, error code {}",
+                code
+            ));
+        }
+
+        Ok(Self { clock_id })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn current() -> Result<Self> {
+        errors::new_error_t("cpu time limits are only supported on linux")
+    }
+
+    #[cfg(target_os = "linux")]
+    fn elapsed(&self) -> Duration {
+        let mut time = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+
+        // SAFETY: `time` is a valid pointer and the clock belongs to a thread that outlives the watchdog.
+        unsafe { libc::clock_gettime(self.clock_id, &mut time) };
+
+        Duration::new(time.tv_sec as u64, time.tv_nsec as u32)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn elapsed(&self) -> Duration {
+        Duration::default()
+    }
+}
@@ -0,0 +1,15 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+mod archive;
+mod disk;
+mod memory;
+mod mount;
+mod quota;
+mod vfs;
+
+pub use archive::*;
+pub use disk::*;
+pub use memory::*;
+pub use mount::*;
+pub use quota::*;
+pub use vfs::*;
@@ -0,0 +1,124 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use super::{MemoryFs, Vfs, VfsDirEntry, VfsFile, VfsMetadata};
+use crate::permissions::fs::FsOpenOptions;
+use log::debug;
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+use tar::{Archive, EntryType};
+use utilities::{
+    errors,
+    result::{Context, Result},
+};
+
+/// The files of an archive, e.g. a packaged tenant artifact. Read-only.
+///
+/// The archive is unpacked into memory when created. Paths in the archive are relative to the root.
+/// SEC: Entries other than files and dirs, e.g. symlinks and hard links, are skipped.
+#[derive(Debug, Clone)]
+pub struct ArchiveFs {
+    fs: MemoryFs,
+}
+
+impl ArchiveFs {
+    /// Unpacks a tar archive.
+    pub fn from_tar(reader: impl Read) -> Result<Self> {
+        let context = "unpacking tar archive";
+        let fs = MemoryFs::new();
+
+        let mut archive = Archive::new(reader);
+        for entry in archive.entries().context(context)? {
+            let mut entry = entry.context(context)?;
+            let path = PathBuf::from(std::path::MAIN_SEPARATOR.to_string())
+                .join(entry.path().context(context)?);
+
+            match entry.header().entry_type() {
+                EntryType::Directory => create_dir_all(&fs, &path)?,
+                EntryType::Regular | EntryType::Continuous => {
+                    if let Some(parent) = path.parent() {
+                        create_dir_all(&fs, parent)?;
+                    }
+
+                    let mut content = vec![];
+                    entry.read_to_end(&mut content).context(context)?;
+
+                    let mut file = fs.open(
+                        &path,
+                        &FsOpenOptions {
+                            write: true,
+                            create: true,
+                            truncate: true,
+                            ..Default::default()
+                        },
+                    )?;
+
+                    file.write_all(&content).context(context)?;
+                }
+                entry_type => debug!("Skipped archive entry {:?} of type {:?}", path, entry_type),
+            }
+        }
+
+        Ok(Self { fs })
+    }
+}
+
+impl Vfs for ArchiveFs {
+    fn open(&self, path: &Path, options: &FsOpenOptions) -> Result<Box<dyn VfsFile>> {
+        if options.write || options.append || options.create || options.truncate {
+            return read_only_error(path);
+        }
+
+        self.fs.open(path, options)
+    }
+
+    fn metadata(&self, path: &Path, follow_last: bool) -> Result<VfsMetadata> {
+        let mut metadata = self.fs.metadata(path, follow_last)?;
+        metadata.readonly = true;
+
+        Ok(metadata)
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<VfsDirEntry>> {
+        self.fs.read_dir(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        read_only_error(path)
+    }
+
+    fn remove(&self, path: &Path, _recursive: bool) -> Result<()> {
+        read_only_error(path)
+    }
+
+    fn rename(&self, from_path: &Path, _to_path: &Path) -> Result<()> {
+        read_only_error(from_path)
+    }
+
+    fn copy(&self, _from_path: &Path, to_path: &Path) -> Result<u64> {
+        read_only_error(to_path)
+    }
+
+    fn writable_len(&self) -> Result<u64> {
+        Ok(0)
+    }
+}
+
+/// Creates a dir and its missing parents.
+fn create_dir_all(fs: &MemoryFs, path: &Path) -> Result<()> {
+    let mut ancestors = path.ancestors().collect::<Vec<_>>();
+    ancestors.reverse();
+
+    for ancestor in ancestors {
+        if fs.metadata(ancestor, true).is_err() {
+            fs.create_dir(ancestor)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_only_error<T>(path: &Path) -> Result<T> {
+    errors::permission_error_t(format!("modifying {:?} in a read-only archive", path))
+}
@@ -0,0 +1,145 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use super::{Vfs, VfsDirEntry, VfsFile, VfsFileType, VfsMetadata};
+use crate::permissions::fs::{FsOpenOptions, FsRoot};
+use std::{
+    fs::{self, File, Metadata},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+use utilities::result::{Context, Result};
+
+/// Files on the host disk beneath a root dir.
+///
+/// SEC: Every operation goes through [`FsRoot`](struct@FsRoot), which works relative to dirs opened beneath the root, so
+/// that neither `..` nor symlinks take paths out of the root.
+#[derive(Debug, Clone)]
+pub struct DiskFs {
+    root: FsRoot,
+}
+
+#[derive(Debug)]
+struct DiskFile(File);
+
+impl DiskFs {
+    pub fn new(root: FsRoot) -> Self {
+        Self { root }
+    }
+}
+
+impl Vfs for DiskFs {
+    fn open(&self, path: &Path, options: &FsOpenOptions) -> Result<Box<dyn VfsFile>> {
+        let file = self.root.open(path, options)?;
+        Ok(Box::new(DiskFile(file)))
+    }
+
+    fn metadata(&self, path: &Path, follow_last: bool) -> Result<VfsMetadata> {
+        self.root.metadata(path, follow_last)
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<VfsDirEntry>> {
+        self.root.read_dir(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        self.root.create_dir(path)
+    }
+
+    fn remove(&self, path: &Path, recursive: bool) -> Result<()> {
+        self.root.remove(path, recursive)
+    }
+
+    fn rename(&self, from_path: &Path, to_path: &Path) -> Result<()> {
+        self.root.rename(from_path, to_path)
+    }
+
+    fn copy(&self, from_path: &Path, to_path: &Path) -> Result<u64> {
+        let context = || format!("copying {:?} to {:?}", from_path, to_path);
+
+        let read_options = FsOpenOptions {
+            read: true,
+            ..Default::default()
+        };
+
+        let write_options = FsOpenOptions {
+            write: true,
+            create: true,
+            truncate: true,
+            ..Default::default()
+        };
+
+        let mut from_file = self.root.open(from_path, &read_options)?;
+        let mut to_file = self.root.open(to_path, &write_options)?;
+
+        // Copy file content and permissions.
+        let permissions = from_file.metadata().context(context())?.permissions();
+        let total_copied = io::copy(&mut from_file, &mut to_file).context(context())?;
+        to_file.set_permissions(permissions).context(context())?;
+
+        Ok(total_copied)
+    }
+}
+
+impl VfsFile for DiskFile {
+    fn set_len(&mut self, len: u64) -> Result<()> {
+        self.0.set_len(len).context("truncating file")
+    }
+
+    fn sync_all(&mut self) -> Result<()> {
+        self.0.sync_all().context("syncing file")
+    }
+
+    fn sync_data(&mut self) -> Result<()> {
+        self.0.sync_data().context("syncing file data")
+    }
+
+    fn metadata(&self) -> Result<VfsMetadata> {
+        let metadata = self.0.metadata().context("getting metadata of file")?;
+        Ok(VfsMetadata::from(&metadata))
+    }
+}
+
+impl Read for DiskFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for DiskFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl Seek for DiskFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+impl From<&Metadata> for VfsMetadata {
+    fn from(metadata: &Metadata) -> Self {
+        Self {
+            file_type: to_file_type(&metadata.file_type()),
+            len: metadata.len(),
+            readonly: metadata.permissions().readonly(),
+            modified: metadata.modified().ok(),
+            accessed: metadata.accessed().ok(),
+            created: metadata.created().ok(),
+        }
+    }
+}
+
+fn to_file_type(file_type: &fs::FileType) -> VfsFileType {
+    if file_type.is_dir() {
+        VfsFileType::Dir
+    } else if file_type.is_symlink() {
+        VfsFileType::Symlink
+    } else {
+        VfsFileType::File
+    }
+}
@@ -0,0 +1,427 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use super::{Vfs, VfsDirEntry, VfsFile, VfsFileType, VfsMetadata};
+use crate::permissions::fs::{Fs, FsOpenOptions};
+use deno_core::parking_lot::Mutex;
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use utilities::{errors, result::Result};
+
+/// The largest a file held in memory can get, in bytes.
+pub const MAX_MEMORY_FILE_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Files held in memory, e.g. for tests or an ephemeral scratch space. Nothing touches the host disk.
+///
+/// Clones share the same files. Files can't grow past [`MAX_MEMORY_FILE_SIZE`](constant@MAX_MEMORY_FILE_SIZE).
+#[derive(Debug, Clone)]
+pub struct MemoryFs {
+    nodes: Arc<Mutex<BTreeMap<PathBuf, Node>>>, // Keyed by clean absolute paths. The root is always present.
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    File(Arc<Mutex<Vec<u8>>>), // Shared with the handles opened on the file.
+    Dir,
+}
+
+#[derive(Debug)]
+struct MemoryFile {
+    data: Arc<Mutex<Vec<u8>>>,
+    position: u64,
+    options: FsOpenOptions,
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(root(), Node::Dir);
+
+        Self {
+            nodes: Arc::new(Mutex::new(nodes)),
+        }
+    }
+}
+
+impl Default for MemoryFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vfs for MemoryFs {
+    fn open(&self, path: &Path, options: &FsOpenOptions) -> Result<Box<dyn VfsFile>> {
+        let path = clean(path)?;
+        let mut nodes = self.nodes.lock();
+
+        let data = match nodes.get(&path) {
+            Some(Node::File(data)) => Arc::clone(data),
+            Some(Node::Dir) => {
+                return errors::new_error_t(format!("opening dir {:?} as a file", path))
+            }
+            None if options.create => {
+                check_parent(&nodes, &path)?;
+
+                let data = Arc::new(Mutex::new(vec![]));
+                nodes.insert(path, Node::File(Arc::clone(&data)));
+                data
+            }
+            None => return not_found(&path),
+        };
+
+        if options.truncate {
+            data.lock().clear();
+        }
+
+        Ok(Box::new(MemoryFile {
+            data,
+            position: 0,
+            options: *options,
+        }))
+    }
+
+    fn metadata(&self, path: &Path, _follow_last: bool) -> Result<VfsMetadata> {
+        let path = clean(path)?;
+
+        match self.nodes.lock().get(&path) {
+            Some(node) => Ok(node.metadata()),
+            None => not_found(&path),
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<VfsDirEntry>> {
+        let path = clean(path)?;
+        let nodes = self.nodes.lock();
+
+        match nodes.get(&path) {
+            Some(Node::Dir) => (),
+            Some(Node::File(_)) => {
+                return errors::new_error_t(format!("reading file {:?} as a dir", path))
+            }
+            None => return not_found(&path),
+        }
+
+        let entries = children(&nodes, &path)
+            .map(|(child_path, node)| VfsDirEntry {
+                name: child_path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                file_type: node.metadata().file_type,
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        let path = clean(path)?;
+        let mut nodes = self.nodes.lock();
+
+        if nodes.contains_key(&path) {
+            return errors::new_error_t(format!("creating dir {:?} that already exists", path));
+        }
+
+        check_parent(&nodes, &path)?;
+        nodes.insert(path, Node::Dir);
+
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path, recursive: bool) -> Result<()> {
+        let path = clean(path)?;
+        let mut nodes = self.nodes.lock();
+
+        if path == root() {
+            return errors::permission_error_t("removing the root dir");
+        }
+
+        match nodes.get(&path) {
+            Some(Node::File(_)) => (),
+            Some(Node::Dir) => {
+                if !recursive && children(&nodes, &path).next().is_some() {
+                    return errors::new_error_t(format!(
+                        "removing dir {:?} that is not empty",
+                        path
+                    ));
+                }
+            }
+            None => return not_found(&path),
+        }
+
+        nodes.retain(|node_path, _| !node_path.starts_with(&path));
+
+        Ok(())
+    }
+
+    fn rename(&self, from_path: &Path, to_path: &Path) -> Result<()> {
+        let from_path = clean(from_path)?;
+        let to_path = clean(to_path)?;
+        let mut nodes = self.nodes.lock();
+
+        if from_path == root() || to_path == root() {
+            return errors::permission_error_t("renaming the root dir");
+        }
+
+        if !nodes.contains_key(&from_path) {
+            return not_found(&from_path);
+        }
+
+        if from_path == to_path {
+            return Ok(());
+        }
+
+        if to_path.starts_with(&from_path) {
+            return errors::new_error_t(format!(
+                "renaming {:?} to a path inside itself {:?}",
+                from_path, to_path
+            ));
+        }
+
+        check_parent(&nodes, &to_path)?;
+
+        // Like on the host disk, only files and empty dirs are replaced.
+        if let Some(Node::Dir) = nodes.get(&to_path) {
+            if children(&nodes, &to_path).next().is_some() {
+                return errors::new_error_t(format!(
+                    "renaming to dir {:?} that is not empty",
+                    to_path
+                ));
+            }
+        }
+
+        nodes.remove(&to_path);
+
+        // Move the node and everything under it.
+        let moved_paths = nodes
+            .keys()
+            .filter(|node_path| node_path.starts_with(&from_path))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for moved_path in moved_paths {
+            let node = nodes.remove(&moved_path).unwrap();
+            let suffix = moved_path.strip_prefix(&from_path).unwrap();
+            nodes.insert(to_path.join(suffix), node);
+        }
+
+        Ok(())
+    }
+
+    fn copy(&self, from_path: &Path, to_path: &Path) -> Result<u64> {
+        let from_path = clean(from_path)?;
+        let to_path = clean(to_path)?;
+        let mut nodes = self.nodes.lock();
+
+        let content = match nodes.get(&from_path) {
+            Some(Node::File(data)) => data.lock().clone(),
+            Some(Node::Dir) => {
+                return errors::new_error_t(format!("copying dir {:?} as a file", from_path))
+            }
+            None => return not_found(&from_path),
+        };
+
+        let total_copied = content.len() as u64;
+
+        match nodes.get(&to_path) {
+            // Handles already opened on the destination see the new content.
+            Some(Node::File(data)) => *data.lock() = content,
+            Some(Node::Dir) => {
+                return errors::new_error_t(format!("copying to dir {:?} as a file", to_path))
+            }
+            None => {
+                check_parent(&nodes, &to_path)?;
+                nodes.insert(to_path, Node::File(Arc::new(Mutex::new(content))));
+            }
+        }
+
+        Ok(total_copied)
+    }
+}
+
+impl Node {
+    fn metadata(&self) -> VfsMetadata {
+        let (file_type, len) = match self {
+            Node::File(data) => (VfsFileType::File, data.lock().len() as u64),
+            Node::Dir => (VfsFileType::Dir, 0),
+        };
+
+        VfsMetadata {
+            file_type,
+            len,
+            readonly: false,
+            modified: None,
+            accessed: None,
+            created: None,
+        }
+    }
+}
+
+impl MemoryFile {
+    fn is_writable(&self) -> bool {
+        self.options.write || self.options.append
+    }
+}
+
+impl VfsFile for MemoryFile {
+    fn set_len(&mut self, len: u64) -> Result<()> {
+        if !self.is_writable() {
+            return errors::new_error_t("truncating file not opened for writing");
+        }
+
+        // SEC: Scripts choose the length, so it is bounded before anything is allocated.
+        if len > MAX_MEMORY_FILE_SIZE {
+            return errors::limit_exceeded_error_t(format!(
+                "maximum file size of {} exceeded",
+                MAX_MEMORY_FILE_SIZE
+            ));
+        }
+
+        let len = to_usize(len)?;
+        self.data.lock().resize(len, 0);
+
+        Ok(())
+    }
+
+    fn sync_all(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn sync_data(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn metadata(&self) -> Result<VfsMetadata> {
+        Ok(Node::File(Arc::clone(&self.data)).metadata())
+    }
+}
+
+impl Read for MemoryFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.options.read {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "file not opened for reading",
+            ));
+        }
+
+        let data = self.data.lock();
+
+        // Reading past the end reads nothing.
+        let start = usize::try_from(self.position)
+            .unwrap_or(usize::MAX)
+            .min(data.len());
+        let total_read = buf.len().min(data.len() - start);
+
+        buf[..total_read].copy_from_slice(&data[start..start + total_read]);
+        self.position = (start + total_read) as u64;
+
+        Ok(total_read)
+    }
+}
+
+impl Write for MemoryFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.is_writable() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "file not opened for writing",
+            ));
+        }
+
+        let mut data = self.data.lock();
+
+        if self.options.append {
+            self.position = data.len() as u64;
+        }
+
+        let start = usize::try_from(self.position)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "position out of range"))?;
+
+        // SEC: Scripts choose the position, so the end is bounded before anything is allocated.
+        let end = match start.checked_add(buf.len()) {
+            Some(end) if end as u64 <= MAX_MEMORY_FILE_SIZE => end,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("maximum file size of {} exceeded", MAX_MEMORY_FILE_SIZE),
+                ))
+            }
+        };
+
+        // Writing past the end fills the gap with zeros.
+        if end > data.len() {
+            data.resize(end, 0);
+        }
+
+        data[start..end].copy_from_slice(buf);
+        self.position = end as u64;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for MemoryFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let len = self.data.lock().len() as i128;
+
+        let position = match pos {
+            SeekFrom::Start(offset) => offset as i128,
+            SeekFrom::End(offset) => len + offset as i128,
+            SeekFrom::Current(offset) => self.position as i128 + offset as i128,
+        };
+
+        if position < 0 || position > u64::MAX as i128 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seeking to an invalid position",
+            ));
+        }
+
+        self.position = position as u64;
+
+        Ok(self.position)
+    }
+}
+
+fn root() -> PathBuf {
+    PathBuf::from(std::path::MAIN_SEPARATOR.to_string())
+}
+
+/// Cleans a path the same way permissions are checked.
+fn clean(path: &Path) -> Result<PathBuf> {
+    Fs::clean_path(&root(), path)
+}
+
+fn children<'a>(
+    nodes: &'a BTreeMap<PathBuf, Node>,
+    path: &'a Path,
+) -> impl Iterator<Item = (&'a PathBuf, &'a Node)> {
+    nodes
+        .iter()
+        .filter(move |(node_path, _)| node_path.parent() == Some(path))
+}
+
+fn check_parent(nodes: &BTreeMap<PathBuf, Node>, path: &Path) -> Result<()> {
+    match path.parent().and_then(|parent| nodes.get(parent)) {
+        Some(Node::Dir) => Ok(()),
+        _ => errors::new_error_t(format!("parent dir of {:?} does not exist", path)),
+    }
+}
+
+fn not_found<T>(path: &Path) -> Result<T> {
+    errors::new_error_t(format!("{:?} does not exist", path))
+}
+
+fn to_usize(len: u64) -> Result<usize> {
+    usize::try_from(len).or_else(|_| errors::new_error_t(format!("length {} out of range", len)))
+}
@@ -0,0 +1,250 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use super::{Vfs, VfsDirEntry, VfsFile, VfsMetadata};
+use crate::permissions::{
+    fs::{Fs, FsOpenOptions},
+    State,
+};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use utilities::{
+    errors,
+    result::{Context, Result},
+};
+
+/// A mount table. Each mount puts a backend at a path, e.g. a host dir at `/code`.
+///
+/// Added to the permissions state. Paths are then checked as they are, without `FsRoot`, and must be in a mount.
+/// SEC: Writes to read-only mounts are rejected by the `Fs` checks whatever the allow lists say, and by the mounts.
+///
+/// It is also the backend scripts see, unless an [`FsBackend`](struct@super::FsBackend) is added too.
+///
+/// ```ignore
+/// let mounts = MountFs::new()
+///     .mount_read_only("/code", DiskFs::new(FsRoot::try_from("/srv/tenant/code")?))?
+///     .mount("/data", DiskFs::new(FsRoot::try_from("/srv/tenant/data")?))?
+///     .mount("/tmp", MemoryFs::new())?;
+///
+/// let permissions = Permissions::builder()
+///     .add_state(mounts)
+///     .add_permissions_with_allow_lists(&[(Fs::Write, &[FsPath::from("/data/**")])])?
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MountFs {
+    mounts: Vec<Mount>,
+}
+
+#[derive(Debug, Clone)]
+struct Mount {
+    path: PathBuf,
+    backend: Arc<dyn Vfs>,
+    read_only: bool,
+}
+
+impl MountFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mount(self, path: impl AsRef<Path>, backend: impl Vfs + 'static) -> Result<Self> {
+        self.add_mount(path.as_ref(), Arc::new(backend), false)
+    }
+
+    pub fn mount_read_only(
+        self,
+        path: impl AsRef<Path>,
+        backend: impl Vfs + 'static,
+    ) -> Result<Self> {
+        self.add_mount(path.as_ref(), Arc::new(backend), true)
+    }
+
+    /// Checks that a path is in a mount, and that the mount is writable if `write` is set.
+    pub fn check(&self, path: &Path, write: bool) -> Result<()> {
+        if write {
+            self.find_writable(path)?;
+        } else {
+            self.find(path)?;
+        }
+
+        Ok(())
+    }
+
+    fn add_mount(mut self, path: &Path, backend: Arc<dyn Vfs>, read_only: bool) -> Result<Self> {
+        if !path.starts_with(std::path::MAIN_SEPARATOR.to_string()) {
+            return errors::new_error_t(format!(
+                r#"expected mount path to be an absolute path starting with a path separator, {:?}"#,
+                path
+            ));
+        }
+
+        let path = Fs::clean_path(&root(), path)?;
+        if self.mounts.iter().any(|mount| mount.path == path) {
+            return errors::new_error_t(format!("path {:?} is already mounted", path));
+        }
+
+        self.mounts.push(Mount {
+            path,
+            backend,
+            read_only,
+        });
+
+        Ok(self)
+    }
+
+    /// Finds the mount a path is in, and the path inside the mount. Nested mounts take precedence.
+    fn find(&self, path: &Path) -> Result<(&Mount, PathBuf)> {
+        let path = Fs::clean_path(&root(), path)?;
+
+        let mount = self
+            .mounts
+            .iter()
+            .filter(|mount| path.starts_with(&mount.path))
+            .max_by_key(|mount| mount.path.components().count());
+
+        match mount {
+            Some(mount) => {
+                let inner_path = root().join(path.strip_prefix(&mount.path).unwrap());
+                Ok((mount, inner_path))
+            }
+            None => errors::permission_error_t(format!("path {:?} is not in a mount", path)),
+        }
+    }
+
+    fn find_writable(&self, path: &Path) -> Result<(&Mount, PathBuf)> {
+        let (mount, inner_path) = self.find(path)?;
+
+        // SEC: Read-only mounts can't be written to, whatever the allow lists say.
+        if mount.read_only {
+            return errors::permission_error_t(format!("path {:?} is in a read-only mount", path));
+        }
+
+        Ok((mount, inner_path))
+    }
+
+    /// Same as `find_writable` but the mount point itself is not accepted.
+    fn find_below_mount_point(&self, path: &Path) -> Result<(&Mount, PathBuf)> {
+        let (mount, inner_path) = self.find_writable(path)?;
+
+        // SEC: Mount points can't be removed or moved.
+        if inner_path == root() {
+            return errors::permission_error_t(format!("path {:?} is a mount point", path));
+        }
+
+        Ok((mount, inner_path))
+    }
+}
+
+impl Vfs for MountFs {
+    fn open(&self, path: &Path, options: &FsOpenOptions) -> Result<Box<dyn VfsFile>> {
+        let (mount, inner_path) =
+            if options.write || options.append || options.create || options.truncate {
+                self.find_writable(path)?
+            } else {
+                self.find(path)?
+            };
+
+        mount.backend.open(&inner_path, options)
+    }
+
+    fn metadata(&self, path: &Path, follow_last: bool) -> Result<VfsMetadata> {
+        let (mount, inner_path) = self.find(path)?;
+
+        let mut metadata = mount.backend.metadata(&inner_path, follow_last)?;
+        metadata.readonly |= mount.read_only;
+
+        Ok(metadata)
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<VfsDirEntry>> {
+        let (mount, inner_path) = self.find(path)?;
+        mount.backend.read_dir(&inner_path)
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        let (mount, inner_path) = self.find_writable(path)?;
+        mount.backend.create_dir(&inner_path)
+    }
+
+    fn remove(&self, path: &Path, recursive: bool) -> Result<()> {
+        let (mount, inner_path) = self.find_below_mount_point(path)?;
+        mount.backend.remove(&inner_path, recursive)
+    }
+
+    fn rename(&self, from_path: &Path, to_path: &Path) -> Result<()> {
+        let (from_mount, from_inner_path) = self.find_below_mount_point(from_path)?;
+        let (to_mount, to_inner_path) = self.find_below_mount_point(to_path)?;
+
+        // Like on the host disk, renames can't cross mounts.
+        if from_mount.path != to_mount.path {
+            return errors::new_error_t(format!(
+                "renaming {:?} to {:?} across mounts",
+                from_path, to_path
+            ));
+        }
+
+        from_mount.backend.rename(&from_inner_path, &to_inner_path)
+    }
+
+    fn copy(&self, from_path: &Path, to_path: &Path) -> Result<u64> {
+        let (from_mount, from_inner_path) = self.find(from_path)?;
+        let (to_mount, to_inner_path) = self.find_writable(to_path)?;
+
+        if from_mount.path == to_mount.path {
+            return from_mount.backend.copy(&from_inner_path, &to_inner_path);
+        }
+
+        // Copy content between backends.
+        let mut from_file = from_mount.backend.open(
+            &from_inner_path,
+            &FsOpenOptions {
+                read: true,
+                ..Default::default()
+            },
+        )?;
+
+        let mut to_file = to_mount.backend.open(
+            &to_inner_path,
+            &FsOpenOptions {
+                write: true,
+                create: true,
+                truncate: true,
+                ..Default::default()
+            },
+        )?;
+
+        io::copy(&mut from_file, &mut to_file)
+            .context(format!("copying {:?} to {:?}", from_path, to_path))
+    }
+
+    /// Only writable mounts count.
+    fn writable_len(&self) -> Result<u64> {
+        let mut total = 0;
+        for mount in self.mounts.iter().filter(|mount| !mount.read_only) {
+            total += mount.backend.writable_len()?;
+        }
+
+        Ok(total)
+    }
+}
+
+impl State for MountFs {
+    fn get_debug(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MountFs")
+            .field("mounts", &self.mounts)
+            .finish()
+    }
+}
+
+impl Into<Box<dyn State>> for MountFs {
+    fn into(self) -> Box<dyn State> {
+        Box::new(self)
+    }
+}
+
+fn root() -> PathBuf {
+    PathBuf::from(std::path::MAIN_SEPARATOR.to_string())
+}
@@ -0,0 +1,213 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use super::Vfs;
+use crate::permissions::State;
+use deno_core::parking_lot::Mutex;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use utilities::{errors, result::Result};
+
+/// Limits on what scripts can write through the fs extension. Added to the permissions state like `FsRoot`.
+///
+/// Clones share their usage, so a quota can be shared by every runtime of a tenant. Exceeding a quota is a limit error.
+///
+/// ```ignore
+/// let quota = FsQuota::new()
+///     .max_bytes_written(100 * 1024 * 1024)
+///     .max_bytes_on_disk(10 * 1024 * 1024)
+///     .max_files_created(1000);
+///
+/// let permissions = Permissions::builder()
+///     .add_state(mounts)
+///     .add_state(quota.clone())
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FsQuota {
+    max_bytes_written: Option<u64>,
+    max_bytes_on_disk: Option<u64>,
+    max_files_created: Option<u64>,
+    usage: Arc<FsQuotaUsage>,
+}
+
+#[derive(Debug, Default)]
+struct FsQuotaUsage {
+    bytes_written: AtomicU64,
+    bytes_on_disk: Mutex<BytesOnDisk>,
+    files_created: AtomicU64,
+}
+
+/// Bytes on disk are measured from the backend when first needed.
+#[derive(Debug, Default)]
+struct BytesOnDisk {
+    current: Option<u64>,
+    measurements: u32,    // Measurements in progress.
+    released_during: u64, // Bytes released while measuring. The measurement may have counted them.
+}
+
+impl FsQuota {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the total bytes written, including bytes that overwrite existing content.
+    pub fn max_bytes_written(mut self, max_bytes_written: u64) -> Self {
+        self.max_bytes_written = Some(max_bytes_written);
+        self
+    }
+
+    /// Limits the total size of the files under the writable parts of the backend, e.g. the writable mounts.
+    pub fn max_bytes_on_disk(mut self, max_bytes_on_disk: u64) -> Self {
+        self.max_bytes_on_disk = Some(max_bytes_on_disk);
+        self
+    }
+
+    /// Limits the number of files and dirs created.
+    pub fn max_files_created(mut self, max_files_created: u64) -> Self {
+        self.max_files_created = Some(max_files_created);
+        self
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.usage.bytes_written.load(Ordering::SeqCst)
+    }
+
+    pub fn files_created(&self) -> u64 {
+        self.usage.files_created.load(Ordering::SeqCst)
+    }
+
+    /// Counts bytes about to be written.
+    pub fn reserve_bytes_written(&self, len: u64) -> Result<()> {
+        if !reserve(&self.usage.bytes_written, self.max_bytes_written, len) {
+            return errors::limit_exceeded_error_t(format!(
+                "maximum bytes written of {} exceeded",
+                self.max_bytes_written.unwrap_or_default()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Gives back bytes that were counted but not written.
+    pub fn release_bytes_written(&self, len: u64) {
+        release(&self.usage.bytes_written, len);
+    }
+
+    /// Counts a file or dir about to be created.
+    pub fn reserve_file_created(&self) -> Result<()> {
+        if !reserve(&self.usage.files_created, self.max_files_created, 1) {
+            return errors::limit_exceeded_error_t(format!(
+                "maximum files created of {} exceeded",
+                self.max_files_created.unwrap_or_default()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Gives back a file that was counted but not created.
+    pub fn release_file_created(&self) {
+        release(&self.usage.files_created, 1);
+    }
+
+    /// Counts the bytes files under `backend` are about to grow by.
+    pub fn reserve_bytes_on_disk(&self, backend: &dyn Vfs, len: u64) -> Result<()> {
+        let max_bytes_on_disk = match self.max_bytes_on_disk {
+            Some(max_bytes_on_disk) => max_bytes_on_disk,
+            None => return Ok(()),
+        };
+
+        // What is already on disk counts too.
+        if self.usage.bytes_on_disk.lock().current.is_none() {
+            self.measure_bytes_on_disk(backend)?;
+        }
+
+        let mut bytes_on_disk = self.usage.bytes_on_disk.lock();
+        let total = bytes_on_disk
+            .current
+            .unwrap_or_default()
+            .saturating_add(len);
+        if total > max_bytes_on_disk {
+            return errors::limit_exceeded_error_t(format!(
+                "maximum bytes on disk of {} exceeded",
+                max_bytes_on_disk
+            ));
+        }
+
+        bytes_on_disk.current = Some(total);
+
+        Ok(())
+    }
+
+    /// Gives back bytes that files under the backend have shrunk by, e.g. when they are truncated or removed.
+    ///
+    /// Before bytes on disk are first measured, what is released is left for the measurement to see.
+    pub fn release_bytes_on_disk(&self, len: u64) {
+        let mut bytes_on_disk = self.usage.bytes_on_disk.lock();
+        match bytes_on_disk.current.as_mut() {
+            Some(current) => *current = current.saturating_sub(len),
+            None if bytes_on_disk.measurements > 0 => bytes_on_disk.released_during += len,
+            None => {}
+        }
+    }
+
+    /// Measures what is on disk under `backend`. The lock is not held while the tree is walked, so other ops sharing
+    /// the quota are not held up.
+    fn measure_bytes_on_disk(&self, backend: &dyn Vfs) -> Result<()> {
+        self.usage.bytes_on_disk.lock().measurements += 1;
+        let measured = backend.writable_len();
+
+        let mut bytes_on_disk = self.usage.bytes_on_disk.lock();
+        bytes_on_disk.measurements -= 1;
+
+        // SEC: Releases during the walk may or may not have been seen by it. They are applied so that nothing freed
+        // stays counted.
+        let measured = measured?.saturating_sub(bytes_on_disk.released_during);
+        if bytes_on_disk.measurements == 0 {
+            bytes_on_disk.released_during = 0;
+        }
+
+        bytes_on_disk.current.get_or_insert(measured);
+
+        Ok(())
+    }
+}
+
+/// Adds `len` to a counter unless that would take it over `max`. Returns whether it was added.
+fn reserve(counter: &AtomicU64, max: Option<u64>, len: u64) -> bool {
+    let result = counter.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |value| {
+        let total = value.saturating_add(len);
+        match max {
+            Some(max) if total > max => None,
+            _ => Some(total),
+        }
+    });
+
+    result.is_ok()
+}
+
+/// Takes `len` off a counter.
+fn release(counter: &AtomicU64, len: u64) {
+    let _ = counter.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |value| {
+        Some(value.saturating_sub(len))
+    });
+}
+
+impl State for FsQuota {
+    fn get_debug(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FsQuota")
+            .field("max_bytes_written", &self.max_bytes_written)
+            .field("max_bytes_on_disk", &self.max_bytes_on_disk)
+            .field("max_files_created", &self.max_files_created)
+            .field("usage", &self.usage)
+            .finish()
+    }
+}
+
+impl Into<Box<dyn State>> for FsQuota {
+    fn into(self) -> Box<dyn State> {
+        Box::new(self)
+    }
+}
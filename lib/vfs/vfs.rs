@@ -0,0 +1,156 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use super::{DiskFs, MountFs};
+use crate::permissions::{
+    fs::{FsOpenOptions, FsRoot},
+    State, StateMap,
+};
+use std::{
+    fmt::Debug,
+    io::{Read, Seek, Write},
+    path::Path,
+    sync::Arc,
+    time::SystemTime,
+};
+use utilities::result::Result;
+
+/// A filesystem that the fs extension and the ESM loader go through.
+///
+/// Paths are absolute paths starting with a separator, e.g. `/examples/js/files.js`. They have been checked against
+/// permissions already, so backends only need to keep them inside their own tree.
+pub trait Vfs: Debug + Send + Sync {
+    fn open(&self, path: &Path, options: &FsOpenOptions) -> Result<Box<dyn VfsFile>>;
+
+    /// Gets the metadata of a path. Symlinks in the last component are only followed if `follow_last` is set.
+    fn metadata(&self, path: &Path, follow_last: bool) -> Result<VfsMetadata>;
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<VfsDirEntry>>;
+
+    fn create_dir(&self, path: &Path) -> Result<()>;
+
+    /// Removes a file or an empty dir, or a dir and its content if `recursive` is set. Symlinks are not followed.
+    fn remove(&self, path: &Path, recursive: bool) -> Result<()>;
+
+    /// Renames a file or dir. Symlinks are not followed.
+    fn rename(&self, from_path: &Path, to_path: &Path) -> Result<()>;
+
+    /// Copies the content of a file and returns the number of bytes copied.
+    fn copy(&self, from_path: &Path, to_path: &Path) -> Result<u64>;
+
+    /// Gets the total size of the files under a path. Symlinks are not followed.
+    fn tree_len(&self, path: &Path) -> Result<u64> {
+        let metadata = self.metadata(path, false)?;
+        if !metadata.is_dir() {
+            return Ok(if metadata.is_file() { metadata.len } else { 0 });
+        }
+
+        let mut total = 0;
+        for entry in self.read_dir(path)? {
+            total += self.tree_len(&path.join(entry.name))?;
+        }
+
+        Ok(total)
+    }
+
+    /// Gets the total size of the files that can be written to. Used by [`FsQuota`](struct@super::FsQuota).
+    fn writable_len(&self) -> Result<u64> {
+        self.tree_len(Path::new(&std::path::MAIN_SEPARATOR.to_string()))
+    }
+}
+
+/// A file opened by a [`Vfs`](trait@Vfs).
+pub trait VfsFile: Read + Write + Seek + Debug + Send {
+    fn set_len(&mut self, len: u64) -> Result<()>;
+
+    fn sync_all(&mut self) -> Result<()>;
+
+    fn sync_data(&mut self) -> Result<()>;
+
+    fn metadata(&self) -> Result<VfsMetadata>;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VfsFileType {
+    File,
+    Dir,
+    Symlink,
+}
+
+#[derive(Debug, Clone)]
+pub struct VfsMetadata {
+    pub file_type: VfsFileType,
+    pub len: u64,
+    pub readonly: bool,
+    pub modified: Option<SystemTime>, // Times are only set if supported by the backend.
+    pub accessed: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VfsDirEntry {
+    pub name: String,
+    pub file_type: VfsFileType,
+}
+
+/// The filesystem scripts see. Added to the permissions state like [`FsRoot`](struct@FsRoot).
+///
+/// Without it, files are in the [`mounts`](struct@MountFs) or, if there are none, on the host disk beneath `FsRoot`.
+/// Permissions are still checked against the mounts or `FsRoot`. With a backend that is not on the host disk, any root
+/// will do.
+///
+/// ```ignore
+/// let permissions = Permissions::builder()
+///     .add_state(FsRoot::try_from("/")?)
+///     .add_state(FsBackend::new(ArchiveFs::from_tar(File::open("bundle.tar")?)?))
+///     .add_permissions_with_allow_lists(&[(Fs::Execute, &[FsPath::from("/**/*.js")])])?
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct FsBackend(Arc<dyn Vfs>);
+
+impl FsBackend {
+    pub fn new(vfs: impl Vfs + 'static) -> Self {
+        Self(Arc::new(vfs))
+    }
+
+    /// Gets the backend in the state, or else the mounts, or else the host disk beneath `FsRoot`.
+    pub fn from_state(state: &StateMap) -> Result<Arc<dyn Vfs>> {
+        if let Some(backend) = state.get::<FsBackend>() {
+            return Ok(Arc::clone(&backend.0));
+        }
+
+        if let Some(mounts) = state.get::<MountFs>() {
+            return Ok(Arc::new(mounts.clone()));
+        }
+
+        let root = state.try_get::<FsRoot>()?;
+
+        Ok(Arc::new(DiskFs::new(root.clone())))
+    }
+}
+
+impl VfsMetadata {
+    pub fn is_file(&self) -> bool {
+        self.file_type == VfsFileType::File
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type == VfsFileType::Dir
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.file_type == VfsFileType::Symlink
+    }
+}
+
+impl State for FsBackend {
+    fn get_debug(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("FsBackend").field(&self.0).finish()
+    }
+}
+
+impl Into<Box<dyn State>> for FsBackend {
+    fn into(self) -> Box<dyn State> {
+        Box::new(self)
+    }
+}
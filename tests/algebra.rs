@@ -0,0 +1,159 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use std::convert::TryFrom;
+use tera::{
+    permissions::{
+        fs::{Fs, FsPath, FsRoot},
+        Permissions,
+    },
+    Runtime,
+};
+use utilities::result::Result;
+
+fn create_permissions(root: &str, allow_list: &[(Fs, &[FsPath])]) -> Result<Permissions> {
+    Ok(Permissions::builder()
+        .add_state(FsRoot::try_from(root)?)
+        .add_permissions_with_allow_lists(allow_list)?
+        .build())
+}
+
+#[test]
+fn intersection_allows_only_what_both_allow() -> Result<()> {
+    let parent = create_permissions(
+        "/",
+        &[
+            (Fs::Read, &[FsPath::from("/data/**")]),
+            (Fs::Write, &[FsPath::from("/data/**")]),
+        ],
+    )?;
+    let child = create_permissions(
+        "/",
+        &[
+            (
+                Fs::Read,
+                &[FsPath::from("/data/public/**"), FsPath::from("/tmp/**")],
+            ),
+            (Fs::Create, &[FsPath::from("/data/**")]),
+        ],
+    )?;
+
+    let permissions = parent.intersection(&child)?;
+
+    assert!(permissions
+        .check(Fs::Read, FsPath::from("/data/public/a.txt"))
+        .is_ok());
+    assert!(permissions
+        .check(Fs::Read, FsPath::from("/data/secrets/a.key"))
+        .is_err());
+    assert!(permissions
+        .check(Fs::Read, FsPath::from("/tmp/a.txt"))
+        .is_err());
+
+    // Permission types missing from either set are dropped.
+    assert!(permissions
+        .check(Fs::Write, FsPath::from("/data/public/a.txt"))
+        .is_err());
+    assert!(permissions
+        .check(Fs::Create, FsPath::from("/data/public/a.txt"))
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn intersection_keeps_narrowing() -> Result<()> {
+    let parent = create_permissions("/", &[(Fs::Read, &[FsPath::from("/data/**")])])?;
+    let child = create_permissions("/", &[(Fs::Read, &[FsPath::from("/data/public/**")])])?;
+    let grandchild = create_permissions("/", &[(Fs::Read, &[FsPath::from("/**/*.txt")])])?;
+
+    let permissions = parent.intersection(&child)?.intersection(&grandchild)?;
+
+    assert!(permissions
+        .check(Fs::Read, FsPath::from("/data/public/a.txt"))
+        .is_ok());
+    assert!(permissions
+        .check(Fs::Read, FsPath::from("/data/public/a.key"))
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn intersection_maps_paths_of_a_set_without_fs_root() -> Result<()> {
+    let parent = create_permissions("/", &[(Fs::Read, &[FsPath::from("/data/**")])])?;
+    let child = Permissions::builder()
+        .add_permissions_with_allow_lists(&[(Fs::Read, &[FsPath::from("/data/public/**")])])?
+        .add_permissions_with_deny_lists(&[(Fs::Read, &[FsPath::from("/data/public/*.key")])])?
+        .build();
+
+    let permissions = parent.intersection(&child)?;
+
+    assert!(permissions
+        .check(Fs::Read, FsPath::from("/data/public/a.txt"))
+        .is_ok());
+    assert!(permissions
+        .check(Fs::Read, FsPath::from("/data/public/a.key"))
+        .is_err());
+    assert!(permissions
+        .check(Fs::Read, FsPath::from("/data/secrets/a.txt"))
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn middleware_is_narrowed_by_a_set_without_fs_root() -> Result<()> {
+    let parent = create_permissions("/", &[(Fs::Read, &[FsPath::from("/data/**")])])?;
+    let child = Permissions::builder()
+        .add_permissions_with_allow_lists(&[(Fs::Read, &[FsPath::from("/data/public/**")])])?
+        .build();
+
+    let mut runtime = Runtime::with_permissions(
+        parent,
+        false,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+    .await?;
+
+    runtime
+        .execute_middleware_script(
+            "/middleware.js",
+            r#"
+        const { query } = Tera.permissions;
+        if (!query("Fs.Read", "/data/public/a.txt") || query("Fs.Read", "/data/secrets/a.txt")) {
+          throw new Error("expected the middleware to be narrowed");
+        }
+        "#,
+            child,
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[test]
+fn union_of_different_fs_roots_is_rejected() -> Result<()> {
+    let root = create_permissions("/", &[(Fs::Read, &[FsPath::from("/data/**")])])?;
+    let other_root = create_permissions(
+        env!("CARGO_MANIFEST_DIR"),
+        &[(Fs::Read, &[FsPath::from("/tests/**")])],
+    )?;
+
+    assert!(root.union(&other_root).is_err());
+    assert!(root.union(&root).is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn union_of_narrowed_permissions_is_rejected() -> Result<()> {
+    let permissions = create_permissions("/", &[(Fs::Read, &[FsPath::from("/data/**")])])?;
+    let narrowed = permissions.intersection(&permissions)?;
+
+    assert!(narrowed.union(&permissions).is_err());
+    assert!(permissions.union(&narrowed).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,124 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+#![cfg(unix)]
+
+use std::{
+    convert::TryFrom,
+    fs,
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
+};
+use tera::permissions::fs::{FsOpenOptions, FsRoot};
+use utilities::result::{Context, Result};
+
+/// A dir under the system temp dir, removed when dropped.
+///
+/// It holds `root/dir/a.txt` and `outside.txt` next to `root`, with symlinks in `root` and `root/dir` to both.
+struct TestDir(PathBuf);
+
+impl TestDir {
+    fn new(name: &str) -> Result<Self> {
+        let path = std::env::temp_dir().join(format!("tera-{}-{}", name, std::process::id()));
+        let test_dir = Self(path);
+        let root = test_dir.root();
+
+        fs::create_dir_all(root.join("dir")).context("creating test dirs")?;
+        fs::write(root.join("dir/a.txt"), "inside").context("creating test file")?;
+        fs::write(test_dir.0.join("outside.txt"), "outside").context("creating test file")?;
+
+        symlink("dir/a.txt", root.join("inside_link")).context("creating test symlink")?;
+        symlink("dir", root.join("dir_link")).context("creating test symlink")?;
+        symlink("../outside.txt", root.join("relative_link")).context("creating test symlink")?;
+        symlink(test_dir.0.join("outside.txt"), root.join("absolute_link"))
+            .context("creating test symlink")?;
+        symlink("..", root.join("dir/parent_link")).context("creating test symlink")?;
+        symlink("../../outside.txt", root.join("dir/escape_link"))
+            .context("creating test symlink")?;
+
+        Ok(test_dir)
+    }
+
+    fn root(&self) -> PathBuf {
+        self.0.join("root")
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn read_options() -> FsOpenOptions {
+    FsOpenOptions {
+        read: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn symlinks_inside_the_root_are_followed() -> Result<()> {
+    let test_dir = TestDir::new("beneath-inside")?;
+    let root = FsRoot::try_from(test_dir.root())?;
+
+    assert!(root
+        .open(Path::new("/inside_link"), &read_options())
+        .is_ok());
+    assert!(root
+        .open(Path::new("/dir_link/a.txt"), &read_options())
+        .is_ok());
+    assert!(root
+        .open(Path::new("/dir/parent_link/dir/a.txt"), &read_options())
+        .is_ok());
+
+    assert!(root.metadata(Path::new("/dir_link"), true)?.is_dir());
+    assert!(root.metadata(Path::new("/dir_link"), false)?.is_symlink());
+    assert_eq!(root.read_dir(Path::new("/dir_link"))?.len(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn symlinks_out_of_the_root_are_refused() -> Result<()> {
+    let test_dir = TestDir::new("beneath-outside")?;
+    let root = FsRoot::try_from(test_dir.root())?;
+
+    assert!(root
+        .open(Path::new("/relative_link"), &read_options())
+        .is_err());
+    assert!(root
+        .open(Path::new("/absolute_link"), &read_options())
+        .is_err());
+    assert!(root
+        .open(Path::new("/dir_link/escape_link"), &read_options())
+        .is_err());
+
+    assert!(root.metadata(Path::new("/relative_link"), true).is_err());
+    assert!(root.metadata(Path::new("/absolute_link"), true).is_err());
+
+    // The links themselves are inside the root.
+    assert!(root
+        .metadata(Path::new("/relative_link"), false)?
+        .is_symlink());
+
+    Ok(())
+}
+
+#[test]
+fn symlinks_are_removed_not_followed() -> Result<()> {
+    let test_dir = TestDir::new("beneath-remove")?;
+    let root = FsRoot::try_from(test_dir.root())?;
+
+    root.remove(Path::new("/relative_link"), false)?;
+    assert!(test_dir.0.join("outside.txt").exists());
+
+    // Nothing outside the dir is reached through `parent_link` or `escape_link` either.
+    root.remove(Path::new("/dir"), true)?;
+    assert!(test_dir.0.join("outside.txt").exists());
+    assert!(test_dir
+        .root()
+        .join("inside_link")
+        .symlink_metadata()
+        .is_ok());
+
+    Ok(())
+}
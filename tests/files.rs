@@ -0,0 +1,142 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use std::{convert::TryFrom, io::Write, path::Path};
+use tera::{
+    permissions::{
+        fs::{Fs, FsOpenOptions, FsPath, FsRoot},
+        Permissions,
+    },
+    vfs::{FsBackend, MemoryFs, Vfs},
+    Runtime,
+};
+use utilities::result::{Context, Result};
+
+/// Creates a memory fs with `/a.txt`.
+fn create_vfs() -> Result<MemoryFs> {
+    let vfs = MemoryFs::new();
+    let options = FsOpenOptions {
+        write: true,
+        create: true,
+        ..Default::default()
+    };
+
+    let mut file = vfs.open(Path::new("/a.txt"), &options)?;
+    file.write_all(b"0123456789").context("writing test file")?;
+
+    Ok(vfs)
+}
+
+async fn create_runtime(vfs: &MemoryFs) -> Result<Runtime> {
+    let allow_list = [FsPath::from("/**")];
+    let permissions = Permissions::builder()
+        .add_state(FsRoot::try_from("/")?)
+        .add_state(FsBackend::new(vfs.clone()))
+        .add_permissions_with_allow_lists(&[
+            (Fs::Open, &allow_list),
+            (Fs::Read, &allow_list),
+            (Fs::Write, &allow_list),
+        ])?
+        .build();
+
+    Runtime::with_permissions(
+        permissions,
+        false,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn files_opened_by_a_module_stay_open_after_it() -> Result<()> {
+    let vfs = create_vfs()?;
+    let mut runtime = create_runtime(&vfs).await?;
+
+    runtime
+        .execute_module(
+            "/open.js",
+            r#"globalThis.file = await Tera.File.open("/a.txt", { read: true });"#,
+        )
+        .await?;
+
+    runtime
+        .execute_module(
+            "/read.js",
+            r#"
+            const content = Tera.decode(await globalThis.file.readAll());
+            if (content !== "0123456789") {
+              throw new Error(`unexpected content ${content}`);
+            }
+            "#,
+        )
+        .await?;
+
+    assert!(runtime.take_leaked_files().is_empty());
+
+    // Until the host closes them.
+    runtime.close_leaked_files();
+    assert_eq!(runtime.take_leaked_files().len(), 1);
+
+    Ok(())
+}
+
+/// Runs a script with the file `/a.txt` open for reading as `file`, and `assertEq` to check values.
+async fn execute_with_file(code: &str) -> Result<()> {
+    let vfs = create_vfs()?;
+    let mut runtime = create_runtime(&vfs).await?;
+
+    let code = format!(
+        r#"
+        const file = await Tera.File.open("/a.txt", {{ read: true }});
+
+        function assertEq(value, expected) {{
+          if (value !== expected) {{
+            throw new Error(`expected ${{expected}}, got ${{value}}`);
+          }}
+        }}
+
+        try {{
+          {}
+        }} finally {{
+          file.close();
+        }}
+        "#,
+        code
+    );
+
+    runtime.execute_module("/test.js", code).await
+}
+
+#[tokio::test]
+async fn seek_from_current_position() -> Result<()> {
+    execute_with_file(
+        r#"
+        assertEq(await file.seek(2), 2);
+        assertEq(await file.seek(3, 1), 5);
+        assertEq(await file.seek(-1, 1), 4);
+        assertEq(Tera.decode(await file.readAll()), "456789");
+        "#,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn seek_from_end() -> Result<()> {
+    execute_with_file(
+        r#"
+        assertEq(await file.seek(-3, 2), 7);
+        assertEq(Tera.decode(await file.readAll()), "789");
+        assertEq(await file.seek(0, 2), 10);
+        "#,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn seek_before_start_is_rejected() -> Result<()> {
+    assert!(execute_with_file("await file.seek(-1);").await.is_err());
+    assert!(execute_with_file("await file.seek(-11, 2);").await.is_err());
+
+    Ok(())
+}
@@ -0,0 +1,126 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use std::convert::TryFrom;
+use tera::permissions::{
+    fs::{Fs, FsPath, FsRoot},
+    glob::Glob,
+    Permissions,
+};
+use utilities::result::Result;
+
+fn is_match(pattern: &str, path: &str) -> bool {
+    Glob::new(pattern).unwrap().is_match(path)
+}
+
+#[test]
+fn regex_metacharacters_are_literal() {
+    assert!(is_match("/data/file.txt", "/data/file.txt"));
+    assert!(!is_match("/data/file.txt", "/data/fileXtxt"));
+
+    assert!(is_match("/data/a+b", "/data/a+b"));
+    assert!(!is_match("/data/a+b", "/data/aab"));
+
+    assert!(is_match("/data/(x|y)", "/data/(x|y)"));
+    assert!(!is_match("/data/(x|y)", "/data/x"));
+
+    assert!(is_match("/data/$HOME^", "/data/$HOME^"));
+    assert!(is_match(r"/data/\[x\]", "/data/[x]"));
+}
+
+#[test]
+fn patterns_match_whole_paths() {
+    assert!(!is_match("/data", "/data2"));
+    assert!(!is_match("/data", "/old/data"));
+    assert!(!is_match("/data/*", "/data/x/y"));
+}
+
+#[test]
+fn wildcards_do_not_cross_separators() {
+    assert!(is_match("/data/*.txt", "/data/a.txt"));
+    assert!(!is_match("/data/*.txt", "/data/secrets/a.txt"));
+
+    assert!(is_match("/data/?.txt", "/data/a.txt"));
+    assert!(!is_match("/data/?.txt", "/data/ab.txt"));
+    assert!(!is_match("/data?x", "/data/x"));
+
+    assert!(!is_match("/data[/]x", "/data/x"));
+    assert!(!is_match("/data[!a]x", "/data/x"));
+    assert!(!is_match("/data[.-0]x", "/data/x"));
+}
+
+#[test]
+fn character_classes() {
+    assert!(is_match("/logs/[0-9].log", "/logs/7.log"));
+    assert!(!is_match("/logs/[0-9].log", "/logs/a.log"));
+
+    assert!(is_match("/logs/[!0-9].log", "/logs/a.log"));
+    assert!(!is_match("/logs/[!0-9].log", "/logs/7.log"));
+
+    assert!(is_match("/logs/[]]", "/logs/]"));
+    assert!(is_match("/logs/[a-]", "/logs/-"));
+    assert!(!is_match("/logs/[a-]", "/logs/b"));
+
+    assert!(Glob::new("/logs/[0-9").is_err());
+    assert!(Glob::new("/logs/[9-0]").is_err());
+}
+
+#[test]
+fn alternation() {
+    assert!(is_match("/data/*.{png,jpg}", "/data/a.png"));
+    assert!(is_match("/data/*.{png,jpg}", "/data/a.jpg"));
+    assert!(!is_match("/data/*.{png,jpg}", "/data/a.gif"));
+    assert!(!is_match("/data/*.{png,jpg}", "/data/a.png,jpg"));
+
+    assert!(is_match("/{public,shared/{a,b}}/x", "/shared/b/x"));
+    assert!(!is_match("/{public,shared/{a,b}}/x", "/shared/c/x"));
+
+    assert!(Glob::new("/data/{a,b").is_err());
+    assert!(Glob::new("/data/a}").is_err());
+}
+
+#[test]
+fn globstar_matches_zero_or_more_segments() {
+    assert!(is_match("/data/**", "/data"));
+    assert!(is_match("/data/**", "/data/a"));
+    assert!(is_match("/data/**", "/data/a/b/c"));
+    assert!(!is_match("/data/**", "/database"));
+
+    assert!(is_match("/data/**/a.txt", "/data/a.txt"));
+    assert!(is_match("/data/**/a.txt", "/data/x/y/a.txt"));
+    assert!(!is_match("/data/**/a.txt", "/data/xa.txt"));
+
+    // Not a whole segment, so the same as `*`.
+    assert!(is_match("/data/**.txt", "/data/a.txt"));
+    assert!(!is_match("/data/**.txt", "/data/x/a.txt"));
+
+    assert!(is_match("**", "/anything/at/all"));
+}
+
+#[test]
+fn newlines_are_matched() {
+    assert!(is_match("/data/**", "/data/a\nb"));
+    assert!(is_match("/data/*", "/data/a\nb"));
+    assert!(!is_match("/data/a", "/data/a\n"));
+}
+
+#[test]
+fn fs_root_is_matched_literally() -> Result<()> {
+    let root = FsRoot::try_from(env!("CARGO_MANIFEST_DIR"))?;
+
+    let permissions = Permissions::builder()
+        .add_state(root)
+        .add_permissions_with_allow_lists(&[(Fs::Read, &[FsPath::from("/examples/*.rs")])])?
+        .build();
+
+    assert!(permissions
+        .check(Fs::Read, FsPath::from("/examples/files.rs"))
+        .is_ok());
+    assert!(permissions
+        .check(Fs::Read, FsPath::from("/examples/js/files.js"))
+        .is_err());
+    assert!(permissions
+        .check(Fs::Read, FsPath::from("/examples/../Cargo.toml"))
+        .is_err());
+
+    Ok(())
+}
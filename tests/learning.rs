@@ -0,0 +1,35 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use std::{convert::TryFrom, sync::Arc};
+use tera::permissions::{
+    fs::{Fs, FsPath, FsRoot},
+    PermissionLearner, PermissionRegistry, Permissions,
+};
+use utilities::result::Result;
+
+#[test]
+fn learned_paths_with_glob_syntax_match_only_themselves() -> Result<()> {
+    let registry = PermissionRegistry::default();
+    let learner = Arc::new(PermissionLearner::new());
+    let permissions = Permissions::builder()
+        .add_state(FsRoot::try_from("/")?)
+        .learn(Arc::clone(&learner))
+        .build();
+
+    permissions.check(Fs::Read, FsPath::from("/data/[a]*{b,c}?.txt"))?;
+
+    let manifest = learner.to_manifest(&registry)?;
+    let learned = Permissions::from_manifest(&manifest, &registry)?;
+
+    assert!(learned
+        .check(Fs::Read, FsPath::from("/data/[a]*{b,c}?.txt"))
+        .is_ok());
+    assert!(learned
+        .check(Fs::Read, FsPath::from("/data/axb?.txt"))
+        .is_err());
+    assert!(learned
+        .check(Fs::Read, FsPath::from("/data/[a]*{b,c}x.txt"))
+        .is_err());
+
+    Ok(())
+}
@@ -0,0 +1,211 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use std::{convert::TryFrom, io::Write, path::Path};
+use tera::{
+    permissions::{
+        fs::{Fs, FsOpenOptions, FsPath, FsRoot},
+        Permissions,
+    },
+    vfs::{FsBackend, MemoryFs, Vfs},
+    Runtime,
+};
+use utilities::result::{Context, Result};
+
+fn create_file(vfs: &MemoryFs, path: &str, content: &[u8]) -> Result<()> {
+    let options = FsOpenOptions {
+        write: true,
+        create: true,
+        ..Default::default()
+    };
+
+    let mut file = vfs.open(Path::new(path), &options)?;
+    file.write_all(content).context("writing test file")
+}
+
+/// Creates a memory fs with `/data/public/a.txt` and `/data/secrets/a.key`.
+fn create_data_dir() -> Result<MemoryFs> {
+    let vfs = MemoryFs::new();
+
+    vfs.create_dir(Path::new("/data"))?;
+    vfs.create_dir(Path::new("/data/public"))?;
+    vfs.create_dir(Path::new("/data/secrets"))?;
+    create_file(&vfs, "/data/public/a.txt", b"public")?;
+    create_file(&vfs, "/data/secrets/a.key", b"secret")?;
+
+    Ok(vfs)
+}
+
+/// Allows writing anything under `/data` except keys in `/data/secrets` and `/data/locked`.
+fn create_permissions(vfs: &MemoryFs) -> Result<Permissions> {
+    let allow_list = [FsPath::from("/data/**")];
+    let deny_list = [
+        FsPath::from("/data/secrets/*.key"),
+        FsPath::from("/data/locked/*.key"),
+    ];
+
+    Ok(Permissions::builder()
+        .add_state(FsRoot::try_from("/")?)
+        .add_state(FsBackend::new(vfs.clone()))
+        .add_permissions_with_allow_lists(&[
+            (Fs::Create, &allow_list),
+            (Fs::Read, &allow_list),
+            (Fs::Write, &allow_list),
+            (Fs::Info, &allow_list),
+        ])?
+        .add_permissions_with_deny_lists(&[(Fs::Write, &deny_list)])?
+        .build())
+}
+
+async fn execute(permissions: Permissions, code: &str) -> Result<()> {
+    let mut runtime = Runtime::with_permissions(
+        permissions,
+        false,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+    .await?;
+
+    runtime.execute_module("/test.js", code).await
+}
+
+#[test]
+fn deny_beats_allow() -> Result<()> {
+    let permissions = create_permissions(&MemoryFs::new())?;
+
+    assert!(permissions
+        .check(Fs::Write, FsPath::from("/data/secrets/a.txt"))
+        .is_ok());
+    assert!(permissions
+        .check(Fs::Write, FsPath::from("/data/secrets/a.key"))
+        .is_err());
+    assert!(permissions
+        .check(Fs::Write, FsPath::from("/data/public/../secrets/a.key"))
+        .is_err());
+
+    // Only the denied permission type is affected.
+    assert!(permissions
+        .check(Fs::Create, FsPath::from("/data/secrets/a.key"))
+        .is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn deny_entries_under_a_dir_are_found() -> Result<()> {
+    let permissions = create_permissions(&MemoryFs::new())?;
+
+    assert!(permissions
+        .check_nested(&[Fs::Write], FsPath::from("/data/public"))
+        .is_ok());
+    assert!(permissions
+        .check_nested(&[Fs::Write], FsPath::from("/data/secrets"))
+        .is_err());
+    assert!(permissions
+        .check_nested(&[Fs::Write], FsPath::from("/data"))
+        .is_err());
+
+    // Deny entries of other permission types do not count.
+    assert!(permissions
+        .check_nested(&[Fs::Create], FsPath::from("/data"))
+        .is_ok());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn recursive_remove_of_dir_with_denied_path_is_rejected() -> Result<()> {
+    let vfs = create_data_dir()?;
+
+    let result = execute(
+        create_permissions(&vfs)?,
+        r#"await Tera.fs.remove("/data", { recursive: true });"#,
+    )
+    .await;
+
+    assert!(result.is_err());
+    assert!(vfs
+        .metadata(Path::new("/data/secrets/a.key"), false)
+        .is_ok());
+
+    // Dirs without denied paths under them can still be removed.
+    execute(
+        create_permissions(&vfs)?,
+        r#"await Tera.fs.remove("/data/public", { recursive: true });"#,
+    )
+    .await?;
+
+    assert!(vfs.metadata(Path::new("/data/public"), false).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn rename_of_dir_with_denied_path_is_rejected() -> Result<()> {
+    let vfs = create_data_dir()?;
+
+    // Denied paths under the source.
+    let result = execute(
+        create_permissions(&vfs)?,
+        r#"await Tera.fs.rename("/data/secrets", "/data/moved");"#,
+    )
+    .await;
+
+    assert!(result.is_err());
+    assert!(vfs
+        .metadata(Path::new("/data/secrets/a.key"), false)
+        .is_ok());
+
+    // Denied paths under the destination.
+    let result = execute(
+        create_permissions(&vfs)?,
+        r#"await Tera.fs.rename("/data/public", "/data/locked");"#,
+    )
+    .await;
+
+    assert!(result.is_err());
+    assert!(vfs.metadata(Path::new("/data/public/a.txt"), false).is_ok());
+
+    // Files are only checked as they are.
+    execute(
+        create_permissions(&vfs)?,
+        r#"await Tera.fs.rename("/data/public/a.txt", "/data/public/b.txt");"#,
+    )
+    .await?;
+
+    assert!(vfs.metadata(Path::new("/data/public/b.txt"), false).is_ok());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn rename_of_file_out_of_denied_dir_is_rejected() -> Result<()> {
+    let vfs = create_data_dir()?;
+    create_file(&vfs, "/data/secrets/a.txt", b"secret")?;
+
+    let allow_list = [FsPath::from("/data/**")];
+    let permissions = Permissions::builder()
+        .add_state(FsRoot::try_from("/")?)
+        .add_state(FsBackend::new(vfs.clone()))
+        .add_permissions_with_allow_lists(&[
+            (Fs::Create, &allow_list),
+            (Fs::Read, &allow_list),
+            (Fs::Write, &allow_list),
+        ])?
+        .add_permissions_with_deny_lists(&[(Fs::Read, &[FsPath::from("/data/secrets/**")])])?
+        .build();
+
+    let result = execute(
+        permissions,
+        r#"await Tera.fs.rename("/data/secrets/a.txt", "/data/a.txt");"#,
+    )
+    .await;
+
+    assert!(result.is_err());
+    assert!(vfs
+        .metadata(Path::new("/data/secrets/a.txt"), false)
+        .is_ok());
+    assert!(vfs.metadata(Path::new("/data/a.txt"), false).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,191 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use std::{convert::TryFrom, io::Write, path::Path};
+use tera::{
+    permissions::{
+        fs::{Fs, FsOpenOptions, FsPath, FsRoot},
+        Permissions,
+    },
+    vfs::{FsBackend, FsQuota, MemoryFs, Vfs},
+    Runtime,
+};
+use utilities::result::{Context, Result};
+
+/// Helpers for the scripts below. `write` appends zeroes to a file and creates it if missing.
+const HELPERS: &str = r#"
+const { File, fs } = Tera;
+
+async function write(path, len) {
+  const file = await File.open(path, { write: true, append: true, create: true });
+  try {
+    await file.writeAll(new Uint8Array(len));
+  } finally {
+    file.close();
+  }
+}
+
+async function truncate(path, len) {
+  const file = await File.open(path, { write: true });
+  try {
+    await file.truncate(len);
+  } finally {
+    file.close();
+  }
+}
+
+async function assertRejects(promise) {
+  try {
+    await promise;
+  } catch {
+    return;
+  }
+
+  throw new Error("expected the promise to reject");
+}
+"#;
+
+fn create_permissions(vfs: &MemoryFs, quota: &FsQuota) -> Result<Permissions> {
+    let allow_list = [FsPath::from("/**")];
+
+    Ok(Permissions::builder()
+        .add_state(FsRoot::try_from("/")?)
+        .add_state(FsBackend::new(vfs.clone()))
+        .add_state(quota.clone())
+        .add_permissions_with_allow_lists(&[
+            (Fs::Open, &allow_list),
+            (Fs::Create, &allow_list),
+            (Fs::Read, &allow_list),
+            (Fs::Write, &allow_list),
+            (Fs::Info, &allow_list),
+        ])?
+        .build())
+}
+
+async fn execute(permissions: Permissions, code: &str) -> Result<()> {
+    let mut runtime = Runtime::with_permissions(
+        permissions,
+        false,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+    .await?;
+
+    runtime
+        .execute_module("/test.js", &format!("{}\n{}", HELPERS, code))
+        .await
+}
+
+#[tokio::test]
+async fn bytes_on_disk_are_given_back() -> Result<()> {
+    let vfs = MemoryFs::new();
+    let quota = FsQuota::new().max_bytes_on_disk(10);
+
+    execute(
+        create_permissions(&vfs, &quota)?,
+        r#"
+        await write("/a.txt", 6);
+        await write("/b.txt", 4);
+        await assertRejects(write("/c.txt", 1));
+
+        // Truncating gives back what the file shrinks by.
+        await truncate("/a.txt", 2);
+        await write("/c.txt", 4);
+        await assertRejects(write("/c.txt", 1));
+
+        // Renaming onto a file gives back the replaced file.
+        await fs.rename("/c.txt", "/b.txt");
+        await write("/a.txt", 4);
+        await assertRejects(write("/a.txt", 1));
+
+        // Removing gives back the removed file.
+        await fs.remove("/b.txt");
+        await write("/c.txt", 4);
+        await assertRejects(write("/c.txt", 1));
+        "#,
+    )
+    .await?;
+
+    assert_eq!(vfs.writable_len()?, 10);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn bytes_written_are_only_counted_when_written() -> Result<()> {
+    let vfs = MemoryFs::new();
+    let quota = FsQuota::new().max_bytes_written(10).max_bytes_on_disk(8);
+
+    execute(
+        create_permissions(&vfs, &quota)?,
+        r#"
+        await write("/a.txt", 6);
+
+        // Writes over the disk quota are not counted as written.
+        await assertRejects(write("/a.txt", 4));
+        await fs.remove("/a.txt");
+        await write("/a.txt", 4);
+
+        // Freed bytes on disk don't give back bytes written.
+        await fs.remove("/a.txt");
+        await assertRejects(write("/a.txt", 1));
+        "#,
+    )
+    .await?;
+
+    assert_eq!(quota.bytes_written(), 10);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_copies_give_back_what_they_counted() -> Result<()> {
+    let vfs = MemoryFs::new();
+    let quota = FsQuota::new().max_bytes_written(10).max_bytes_on_disk(10);
+
+    execute(
+        create_permissions(&vfs, &quota)?,
+        r#"
+        await write("/a.txt", 5);
+
+        // The destination dir is missing.
+        await assertRejects(fs.copy("/a.txt", "/missing/b.txt"));
+
+        await write("/c.txt", 5);
+        "#,
+    )
+    .await?;
+
+    assert_eq!(quota.bytes_written(), 10);
+    assert_eq!(vfs.writable_len()?, 10);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn bytes_on_disk_released_before_first_write_are_not_counted() -> Result<()> {
+    let vfs = MemoryFs::new();
+    let options = FsOpenOptions {
+        write: true,
+        create: true,
+        ..Default::default()
+    };
+
+    let mut file = vfs.open(Path::new("/a.txt"), &options)?;
+    file.write_all(&[0; 8]).context("writing test file")?;
+
+    let quota = FsQuota::new().max_bytes_on_disk(10);
+
+    execute(
+        create_permissions(&vfs, &quota)?,
+        r#"
+        await truncate("/a.txt", 2);
+        await fs.remove("/a.txt");
+        await write("/b.txt", 10);
+        await assertRejects(write("/b.txt", 1));
+        "#,
+    )
+    .await?;
+
+    Ok(())
+}
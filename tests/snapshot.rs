@@ -0,0 +1,62 @@
+// Copyright 2021 the Gigamono authors. All rights reserved. GPL-3.0 License.
+
+use std::{cell::RefCell, rc::Rc};
+use tera::{events::Events, permissions::Permissions, Runtime, SnapshotPolicy};
+use utilities::result::Result;
+
+#[tokio::test]
+async fn default_builder_uses_embedded_snapshot() -> Result<()> {
+    let runtime = Runtime::builder()
+        .snapshot_policy(SnapshotPolicy::InMemory)
+        .build()
+        .await?;
+
+    assert!(runtime.uses_embedded_snapshot());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn with_permissions_uses_embedded_snapshot() -> Result<()> {
+    let runtime = Runtime::with_permissions(
+        Permissions::default(),
+        SnapshotPolicy::InMemory,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+    .await?;
+
+    assert!(runtime.uses_embedded_snapshot());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn with_events_uses_embedded_snapshot() -> Result<()> {
+    let runtime = Runtime::with_events(
+        Permissions::default(),
+        Rc::new(RefCell::new(Events::default())),
+        SnapshotPolicy::InMemory,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    )
+    .await?;
+
+    assert!(runtime.uses_embedded_snapshot());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn disabled_policy_uses_no_snapshot() -> Result<()> {
+    let runtime = Runtime::builder()
+        .snapshot_policy(SnapshotPolicy::Disabled)
+        .build()
+        .await?;
+
+    assert!(!runtime.uses_embedded_snapshot());
+
+    Ok(())
+}